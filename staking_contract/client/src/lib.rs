@@ -0,0 +1,25 @@
+//! Builds the instructions of the staking and vesting programs, derives their addresses and
+//! reads their accounts, for backends that talk to the programs from Rust.
+//!
+//! ```ignore
+//! let pool = state::pool(&rpc.get_account_data(&pool_address)?)?;
+//! let keys = staking::PoolKeys::new(pool_address, &pool);
+//! let stake = staking::stake(&keys, &owner, &owner_token_account, amount, now, 0);
+//!
+//! let staked = state::token_account(&rpc.get_account_data(&keys.staking_vault)?)?.amount;
+//! let funded = state::token_account(&rpc.get_account_data(&keys.reward_vault)?)?.amount;
+//! let user = state::user(&rpc.get_account_data(&keys.user(&owner))?)?;
+//! let claimable = rewards::pending_user_reward(&pool, &user, staked, funded, now);
+//! ```
+//!
+//! The staking program is built with Anchor 0.19 and the vesting program with Anchor 0.20, so
+//! each side goes through its own `anchor-lang`.
+
+pub mod pda;
+pub mod rewards;
+pub mod staking;
+pub mod state;
+pub mod vesting;
+
+pub use solana_vesting;
+pub use staking_contract;
@@ -0,0 +1,20 @@
+//! Addresses derived from the seeds of both programs. Each helper takes the id of the program
+//! it derives for and returns the address with its bump.
+
+use solana_program::pubkey::Pubkey;
+
+pub use solana_vesting::{find_investor_account, find_vault_account, find_vault_authority, find_vesting_account};
+pub use staking_contract::{
+    find_merchant, find_merchant_user, find_pool_signer, find_reward_vault, find_staking_vault, find_user,
+    find_voter_weight_record,
+};
+
+/// The associated token account of `wallet` for `mint`. The address depends on the token
+/// program, which the `spl-associated-token-account` helpers fix to the SPL Token program.
+pub fn associated_token_address(wallet: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[wallet.as_ref(), token_program.as_ref(), mint.as_ref()],
+        &spl_associated_token_account::ID,
+    )
+    .0
+}
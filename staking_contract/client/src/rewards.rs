@@ -0,0 +1,54 @@
+//! Previews of rewards, computed with the pool's own reward calculator. `total_staked` is the
+//! balance of the pool's staking vault, `reward_vault_balance` that of its reward vault and `now`
+//! the unix timestamp to preview at.
+//!
+//! The figures match what the program would compute at `now`. To read them from the chain
+//! instead, simulate `staking::pending_reward` or `staking::effective_apr` and decode the return
+//! data with `pending_reward_from_return_data` or `effective_apr_bps_from_return_data`.
+
+use std::convert::TryInto;
+
+pub use staking_contract::calculator::{PoolRewards, Position};
+use staking_contract::{Merchant, Pool, User};
+
+/// The reward `user` would have pending after an update at `now`. `claim` pays it out as far
+/// as the reward vault covers it.
+pub fn pending_user_reward(
+    pool: &Pool,
+    user: &User,
+    total_staked: u64,
+    reward_vault_balance: u64,
+    now: u64,
+) -> u64 {
+    pool.rewards(reward_vault_balance)
+        .user_earned_amount(&user.position(), total_staked, now)
+}
+
+/// The reward `merchant` would have pending after an update at `now`
+pub fn pending_merchant_reward(
+    pool: &Pool,
+    merchant: &Merchant,
+    total_staked: u64,
+    reward_vault_balance: u64,
+    now: u64,
+) -> u64 {
+    pool.rewards(reward_vault_balance)
+        .merchant_earned_amount(&merchant.position(), total_staked, now)
+}
+
+/// The pool's reward rate at `now` annualised, in basis points of the total staked
+pub fn effective_apr_bps(pool: &Pool, total_staked: u64, reward_vault_balance: u64, now: u64) -> u64 {
+    let mut rewards = pool.rewards(reward_vault_balance);
+    rewards.accrue(total_staked, now);
+    rewards.effective_apr_bps(total_staked, now)
+}
+
+/// The pending reward set as return data by the `pending_reward` instruction
+pub fn pending_reward_from_return_data(data: &[u8]) -> Option<u64> {
+    Some(u64::from_le_bytes(data.try_into().ok()?))
+}
+
+/// The effective APR set as return data by the `effective_apr` instruction
+pub fn effective_apr_bps_from_return_data(data: &[u8]) -> Option<u64> {
+    Some(u64::from_le_bytes(data.try_into().ok()?))
+}
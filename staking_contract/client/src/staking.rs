@@ -0,0 +1,589 @@
+//! Instructions of the staking program. The accounts a program can derive itself are derived
+//! here; the rest are arguments.
+
+use anchor_lang::{InstructionData, ToAccountMetas};
+use solana_program::instruction::Instruction;
+use solana_program::pubkey::Pubkey;
+use solana_program::{system_program, sysvar};
+use staking_contract::{accounts, instruction, CalculatorKind, Pool, ID};
+
+use crate::pda::{
+    find_merchant, find_merchant_user, find_pool_signer, find_reward_vault, find_staking_vault, find_user,
+    find_voter_weight_record,
+};
+
+/// The addresses of a pool its instructions take, read from the pool account
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PoolKeys {
+    pub pool: Pubkey,
+    pub pool_signer: Pubkey,
+    pub staking_mint: Pubkey,
+    pub staking_vault: Pubkey,
+    pub staking_token_program: Pubkey,
+    pub reward_mint: Pubkey,
+    pub reward_vault: Pubkey,
+    pub reward_token_program: Pubkey,
+}
+
+impl PoolKeys {
+    pub fn new(pool: Pubkey, state: &Pool) -> Self {
+        PoolKeys {
+            pool,
+            pool_signer: find_pool_signer(&pool, &ID).0,
+            staking_mint: state.staking_mint,
+            staking_vault: state.staking_vault,
+            staking_token_program: state.staking_token_program,
+            reward_mint: state.reward_mint,
+            reward_vault: state.reward_vault,
+            reward_token_program: state.reward_token_program,
+        }
+    }
+
+    /// The user account of `owner` in the pool
+    pub fn user(&self, owner: &Pubkey) -> Pubkey {
+        find_user(owner, &self.pool, &ID).0
+    }
+
+    /// The merchant account of `owner` in the pool
+    pub fn merchant(&self, owner: &Pubkey) -> Pubkey {
+        find_merchant(owner, &self.pool, &ID).0
+    }
+
+    /// The account of `owner` staking to `merchant` in the pool
+    pub fn merchant_user(&self, owner: &Pubkey, merchant: &Pubkey) -> Pubkey {
+        find_merchant_user(owner, merchant, &self.pool, &ID).0
+    }
+}
+
+/// The settings of a pool created by `initialize_main_pool`
+#[derive(Clone, Debug)]
+pub struct NewPool {
+    pub staking_mint: Pubkey,
+    pub staking_token_program: Pubkey,
+    pub reward_mint: Pubkey,
+    pub reward_token_program: Pubkey,
+    /// A token account of the authority, only used to derive the vault addresses
+    pub pool_token_ata: Pubkey,
+    pub reward_duration: u64,
+    pub calculator_kind: CalculatorKind,
+    pub target_apr_bps: u64,
+    pub max_emission_rate: u64,
+}
+
+fn staking_instruction(accounts: impl ToAccountMetas, data: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+/// Creates a pool at `pool`, a new account that signs the transaction
+pub fn initialize_main_pool(authority: &Pubkey, pool: &Pubkey, config: &NewPool) -> Instruction {
+    let (pool_signer, pool_nonce) = find_pool_signer(pool, &ID);
+    staking_instruction(
+        accounts::InitializeMainPool {
+            authority: *authority,
+            staking_mint: config.staking_mint,
+            staking_vault: find_staking_vault(&config.pool_token_ata, &ID).0,
+            reward_mint: config.reward_mint,
+            reward_vault: find_reward_vault(&config.pool_token_ata, &ID).0,
+            pool_token_ata: config.pool_token_ata,
+            pool_signer,
+            pool: *pool,
+            staking_token_program: config.staking_token_program,
+            reward_token_program: config.reward_token_program,
+            rent: sysvar::rent::ID,
+            system_program: system_program::ID,
+        },
+        instruction::InitializeMainPool {
+            pool_nonce,
+            reward_duration: config.reward_duration,
+            calculator_kind: config.calculator_kind,
+            target_apr_bps: config.target_apr_bps,
+            max_emission_rate: config.max_emission_rate,
+        },
+    )
+}
+
+pub fn create_user(keys: &PoolKeys, owner: &Pubkey, payer: &Pubkey, current_ts: i64) -> Instruction {
+    let (user, nonce) = find_user(owner, &keys.pool, &ID);
+    staking_instruction(
+        accounts::CreateUser {
+            pool: keys.pool,
+            user,
+            owner: *owner,
+            payer: *payer,
+            system_program: system_program::ID,
+        },
+        instruction::CreateUser { nonce, current_ts },
+    )
+}
+
+pub fn pause(keys: &PoolKeys, authority: &Pubkey) -> Instruction {
+    staking_instruction(
+        accounts::Pause {
+            pool: keys.pool,
+            authority: *authority,
+            pool_signer: keys.pool_signer,
+            token_program: spl_token::ID,
+        },
+        instruction::Pause {},
+    )
+}
+
+pub fn unpause(keys: &PoolKeys, authority: &Pubkey) -> Instruction {
+    staking_instruction(
+        accounts::Unpause {
+            pool: keys.pool,
+            authority: *authority,
+            pool_signer: keys.pool_signer,
+            token_program: spl_token::ID,
+        },
+        instruction::Unpause {},
+    )
+}
+
+pub fn set_target_apr(keys: &PoolKeys, authority: &Pubkey, target_apr_bps: u64, max_emission_rate: u64) -> Instruction {
+    staking_instruction(
+        accounts::SetTargetApr {
+            pool: keys.pool,
+            authority: *authority,
+            staking_vault: keys.staking_vault,
+            reward_vault: keys.reward_vault,
+        },
+        instruction::SetTargetApr {
+            target_apr_bps,
+            max_emission_rate,
+        },
+    )
+}
+
+pub fn set_realm(keys: &PoolKeys, authority: &Pubkey, realm: Pubkey) -> Instruction {
+    staking_instruction(
+        accounts::SetRealm {
+            pool: keys.pool,
+            authority: *authority,
+        },
+        instruction::SetRealm { realm },
+    )
+}
+
+pub fn add_emission_phase(
+    keys: &PoolKeys,
+    authority: &Pubkey,
+    start_ts: u64,
+    end_ts: u64,
+    reward_rate: u64,
+) -> Instruction {
+    staking_instruction(
+        accounts::AddEmissionPhase {
+            pool: keys.pool,
+            authority: *authority,
+            staking_vault: keys.staking_vault,
+            reward_vault: keys.reward_vault,
+        },
+        instruction::AddEmissionPhase {
+            start_ts,
+            end_ts,
+            reward_rate,
+        },
+    )
+}
+
+fn stake_accounts(keys: &PoolKeys, owner: &Pubkey, token_account: &Pubkey) -> accounts::Stake {
+    accounts::Stake {
+        pool: keys.pool,
+        staking_vault: keys.staking_vault,
+        reward_vault: keys.reward_vault,
+        staking_mint: keys.staking_mint,
+        user: keys.user(owner),
+        owner: *owner,
+        stake_from_account: *token_account,
+        pool_signer: keys.pool_signer,
+        token_program: keys.staking_token_program,
+    }
+}
+
+/// Stakes `amount` from `stake_from_account`, locked for `locking_period` seconds
+pub fn stake(
+    keys: &PoolKeys,
+    owner: &Pubkey,
+    stake_from_account: &Pubkey,
+    amount: u64,
+    current_ts: i64,
+    locking_period: i64,
+) -> Instruction {
+    staking_instruction(
+        stake_accounts(keys, owner, stake_from_account),
+        instruction::Stake {
+            amount,
+            current_ts,
+            locking_period,
+        },
+    )
+}
+
+/// Returns `spt_amount` of the stake to `stake_to_account`
+pub fn unstake(keys: &PoolKeys, owner: &Pubkey, stake_to_account: &Pubkey, spt_amount: u64, current_ts: i64) -> Instruction {
+    staking_instruction(
+        stake_accounts(keys, owner, stake_to_account),
+        instruction::Unstake { spt_amount, current_ts },
+    )
+}
+
+fn stake_locked_accounts(
+    keys: &PoolKeys,
+    owner: &Pubkey,
+    lock_authority: &Pubkey,
+    locked_token_account: &Pubkey,
+) -> accounts::StakeLocked {
+    accounts::StakeLocked {
+        pool: keys.pool,
+        staking_vault: keys.staking_vault,
+        reward_vault: keys.reward_vault,
+        staking_mint: keys.staking_mint,
+        user: keys.user(owner),
+        owner: *owner,
+        lock_authority: *lock_authority,
+        locked_token_account: *locked_token_account,
+        pool_signer: keys.pool_signer,
+        token_program: keys.staking_token_program,
+    }
+}
+
+/// Stakes `amount` for `owner` as a position only `lock_authority` can unstake
+pub fn stake_locked(
+    keys: &PoolKeys,
+    owner: &Pubkey,
+    lock_authority: &Pubkey,
+    locked_token_account: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    staking_instruction(
+        stake_locked_accounts(keys, owner, lock_authority, locked_token_account),
+        instruction::StakeLocked { amount },
+    )
+}
+
+pub fn unstake_locked(
+    keys: &PoolKeys,
+    owner: &Pubkey,
+    lock_authority: &Pubkey,
+    locked_token_account: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    staking_instruction(
+        stake_locked_accounts(keys, owner, lock_authority, locked_token_account),
+        instruction::UnstakeLocked { amount },
+    )
+}
+
+pub fn fund(keys: &PoolKeys, funder: &Pubkey, from: &Pubkey, amount: u64) -> Instruction {
+    staking_instruction(
+        accounts::Fund {
+            pool: keys.pool,
+            staking_vault: keys.staking_vault,
+            reward_vault: keys.reward_vault,
+            reward_mint: keys.reward_mint,
+            funder: *funder,
+            from: *from,
+            pool_signer: keys.pool_signer,
+            token_program: keys.reward_token_program,
+        },
+        instruction::Fund { amount },
+    )
+}
+
+pub fn claim(keys: &PoolKeys, owner: &Pubkey, reward_account: &Pubkey, current_ts: i64) -> Instruction {
+    staking_instruction(
+        accounts::ClaimReward {
+            pool: keys.pool,
+            staking_vault: keys.staking_vault,
+            reward_vault: keys.reward_vault,
+            reward_mint: keys.reward_mint,
+            user: keys.user(owner),
+            owner: *owner,
+            reward_account: *reward_account,
+            pool_signer: keys.pool_signer,
+            token_program: keys.reward_token_program,
+        },
+        instruction::Claim { current_ts },
+    )
+}
+
+pub fn reward_shortfall(keys: &PoolKeys) -> Instruction {
+    staking_instruction(
+        accounts::RewardShortfall {
+            pool: keys.pool,
+            staking_vault: keys.staking_vault,
+            reward_vault: keys.reward_vault,
+        },
+        instruction::RewardShortfall {},
+    )
+}
+
+/// Reports the reward `owner` has pending in the pool. Meant to be simulated; the figure comes
+/// back as return data.
+pub fn pending_reward(keys: &PoolKeys, owner: &Pubkey) -> Instruction {
+    staking_instruction(
+        accounts::PendingReward {
+            pool: keys.pool,
+            staking_vault: keys.staking_vault,
+            reward_vault: keys.reward_vault,
+            user: keys.user(owner),
+        },
+        instruction::PendingReward {},
+    )
+}
+
+/// Reports the pool's current reward rate annualised, in basis points. Meant to be simulated;
+/// the figure comes back as return data.
+pub fn effective_apr(keys: &PoolKeys) -> Instruction {
+    staking_instruction(
+        accounts::EffectiveApr {
+            pool: keys.pool,
+            staking_vault: keys.staking_vault,
+            reward_vault: keys.reward_vault,
+        },
+        instruction::EffectiveApr {},
+    )
+}
+
+pub fn close_user(keys: &PoolKeys, owner: &Pubkey) -> Instruction {
+    staking_instruction(
+        accounts::CloseUser {
+            pool: keys.pool,
+            user: keys.user(owner),
+            owner: *owner,
+        },
+        instruction::CloseUser {},
+    )
+}
+
+/// Closes the pool, sending what is left in the staking vault to `staking_refundee` and the
+/// rent to `refundee`
+pub fn close_pool(keys: &PoolKeys, authority: &Pubkey, refundee: &Pubkey, staking_refundee: &Pubkey) -> Instruction {
+    staking_instruction(
+        accounts::ClosePool {
+            refundee: *refundee,
+            staking_refundee: *staking_refundee,
+            pool: keys.pool,
+            authority: *authority,
+            staking_vault: keys.staking_vault,
+            reward_vault: keys.reward_vault,
+            staking_mint: keys.staking_mint,
+            pool_signer: keys.pool_signer,
+            token_program: keys.staking_token_program,
+        },
+        instruction::ClosePool {},
+    )
+}
+
+pub fn initialize_merchant_pool(keys: &PoolKeys, owner: &Pubkey, merchant_name: String, current_ts: i64) -> Instruction {
+    let (merchant, merchant_nonce) = find_merchant(owner, &keys.pool, &ID);
+    staking_instruction(
+        accounts::InitializeMerchantPool {
+            pool: keys.pool,
+            merchant,
+            owner: *owner,
+            system_program: system_program::ID,
+        },
+        instruction::InitializeMerchantPool {
+            merchant_name,
+            merchant_nonce,
+            current_ts,
+        },
+    )
+}
+
+pub fn create_merchant_user(keys: &PoolKeys, owner: &Pubkey, merchant: &Pubkey, current_ts: i64) -> Instruction {
+    let (merchant_user, nonce) = find_merchant_user(owner, merchant, &keys.pool, &ID);
+    staking_instruction(
+        accounts::CreateMerchantUser {
+            pool: keys.pool,
+            merchant: *merchant,
+            merchant_user,
+            owner: *owner,
+            system_program: system_program::ID,
+        },
+        instruction::CreateMerchantUser { nonce, current_ts },
+    )
+}
+
+fn merchant_stake_accounts(
+    keys: &PoolKeys,
+    owner: &Pubkey,
+    merchant: &Pubkey,
+    token_account: &Pubkey,
+) -> accounts::StakeTokenToMerchant {
+    accounts::StakeTokenToMerchant {
+        pool: keys.pool,
+        staking_vault: keys.staking_vault,
+        reward_vault: keys.reward_vault,
+        staking_mint: keys.staking_mint,
+        merchant: *merchant,
+        merchant_user: keys.merchant_user(owner, merchant),
+        owner: *owner,
+        stake_from_account: *token_account,
+        pool_signer: keys.pool_signer,
+        token_program: keys.staking_token_program,
+    }
+}
+
+pub fn stake_token_to_merchant(
+    keys: &PoolKeys,
+    owner: &Pubkey,
+    merchant: &Pubkey,
+    stake_from_account: &Pubkey,
+    amount: u64,
+    current_ts: i64,
+    locking_period: i64,
+) -> Instruction {
+    staking_instruction(
+        merchant_stake_accounts(keys, owner, merchant, stake_from_account),
+        instruction::StakeTokenToMerchant {
+            amount,
+            current_ts,
+            locking_period,
+        },
+    )
+}
+
+pub fn unstake_token_to_merchant(
+    keys: &PoolKeys,
+    owner: &Pubkey,
+    merchant: &Pubkey,
+    stake_to_account: &Pubkey,
+    amount: u64,
+    current_ts: i64,
+) -> Instruction {
+    staking_instruction(
+        merchant_stake_accounts(keys, owner, merchant, stake_to_account),
+        instruction::UnstakeTokenToMerchant { amount, current_ts },
+    )
+}
+
+pub fn pause_merchant(keys: &PoolKeys, owner: &Pubkey, merchant: &Pubkey) -> Instruction {
+    staking_instruction(
+        accounts::PauseMerchant {
+            pool: keys.pool,
+            merchant: *merchant,
+            owner: *owner,
+            pool_signer: keys.pool_signer,
+            token_program: spl_token::ID,
+        },
+        instruction::PauseMerchant {},
+    )
+}
+
+pub fn unpause_merchant(keys: &PoolKeys, owner: &Pubkey, merchant: &Pubkey) -> Instruction {
+    staking_instruction(
+        accounts::UnpauseMerchant {
+            pool: keys.pool,
+            merchant: *merchant,
+            owner: *owner,
+            pool_signer: keys.pool_signer,
+            token_program: spl_token::ID,
+        },
+        instruction::UnpauseMerchant {},
+    )
+}
+
+pub fn claim_reward_for_merchant(
+    keys: &PoolKeys,
+    owner: &Pubkey,
+    merchant: &Pubkey,
+    reward_account: &Pubkey,
+    current_ts: i64,
+) -> Instruction {
+    staking_instruction(
+        accounts::ClaimRewardForMerchant {
+            pool: keys.pool,
+            merchant: *merchant,
+            staking_vault: keys.staking_vault,
+            reward_vault: keys.reward_vault,
+            reward_mint: keys.reward_mint,
+            owner: *owner,
+            reward_account: *reward_account,
+            pool_signer: keys.pool_signer,
+            token_program: keys.reward_token_program,
+        },
+        instruction::ClaimRewardForMerchant { current_ts },
+    )
+}
+
+/// Stakes `amount` from `owner`'s `stake_from_account` for the user account of `beneficiary`
+pub fn stake_on_behalf(
+    keys: &PoolKeys,
+    owner: &Pubkey,
+    beneficiary: &Pubkey,
+    stake_from_account: &Pubkey,
+    amount: u64,
+    current_ts: i64,
+) -> Instruction {
+    staking_instruction(
+        accounts::StakeOnBehalf {
+            pool: keys.pool,
+            staking_vault: keys.staking_vault,
+            reward_vault: keys.reward_vault,
+            staking_mint: keys.staking_mint,
+            user: keys.user(beneficiary),
+            owner: *owner,
+            stake_from_account: *stake_from_account,
+            pool_signer: keys.pool_signer,
+            token_program: keys.staking_token_program,
+        },
+        instruction::StakeOnBehalf { amount, current_ts },
+    )
+}
+
+/// Withdraws the stake made on behalf of `owner` at `list_index` once it is unlocked
+pub fn withdraw(keys: &PoolKeys, owner: &Pubkey, stake_to_account: &Pubkey, list_index: u32, current_ts: i64) -> Instruction {
+    staking_instruction(
+        accounts::Withdraw {
+            pool: keys.pool,
+            staking_vault: keys.staking_vault,
+            reward_vault: keys.reward_vault,
+            staking_mint: keys.staking_mint,
+            user: keys.user(owner),
+            owner: *owner,
+            stake_from_account: *stake_to_account,
+            pool_signer: keys.pool_signer,
+            token_program: keys.staking_token_program,
+        },
+        instruction::Withdraw { list_index, current_ts },
+    )
+}
+
+pub fn create_voter_weight_record(
+    keys: &PoolKeys,
+    owner: &Pubkey,
+    realm: Pubkey,
+    governing_token_mint: Pubkey,
+) -> Instruction {
+    staking_instruction(
+        accounts::CreateVoterWeightRecord {
+            pool: keys.pool,
+            voter_weight_record: find_voter_weight_record(&keys.pool, owner, &ID).0,
+            owner: *owner,
+            system_program: system_program::ID,
+        },
+        instruction::CreateVoterWeightRecord {
+            realm,
+            governing_token_mint,
+        },
+    )
+}
+
+pub fn update_voter_weight_record(keys: &PoolKeys, owner: &Pubkey) -> Instruction {
+    staking_instruction(
+        accounts::UpdateVoterWeightRecord {
+            pool: keys.pool,
+            user: keys.user(owner),
+            voter_weight_record: find_voter_weight_record(&keys.pool, owner, &ID).0,
+            owner: *owner,
+        },
+        instruction::UpdateVoterWeightRecord {},
+    )
+}
@@ -0,0 +1,47 @@
+//! Deserializers for the accounts of both programs, from the raw account data
+
+use anchor_lang::AccountDeserialize as _;
+use anchor_lang_vesting::AccountDeserialize as _;
+use solana_program::program_error::ProgramError;
+use solana_program::program_pack::Pack;
+use solana_vesting::{InvestorAccount, VestingAccount};
+use spl_token::state::Account as TokenAccount;
+use staking_contract::{Merchant, MerchantUser, Pool, User, VoterWeightRecord};
+
+pub fn pool(data: &[u8]) -> Result<Pool, ProgramError> {
+    Pool::try_deserialize(&mut &data[..])
+}
+
+pub fn user(data: &[u8]) -> Result<User, ProgramError> {
+    User::try_deserialize(&mut &data[..])
+}
+
+pub fn merchant(data: &[u8]) -> Result<Merchant, ProgramError> {
+    Merchant::try_deserialize(&mut &data[..])
+}
+
+pub fn merchant_user(data: &[u8]) -> Result<MerchantUser, ProgramError> {
+    MerchantUser::try_deserialize(&mut &data[..])
+}
+
+pub fn voter_weight_record(data: &[u8]) -> Result<VoterWeightRecord, ProgramError> {
+    VoterWeightRecord::try_deserialize(&mut &data[..])
+}
+
+pub fn investor_account(data: &[u8]) -> Result<InvestorAccount, ProgramError> {
+    InvestorAccount::try_deserialize(&mut &data[..])
+}
+
+pub fn vesting_account(data: &[u8]) -> Result<VestingAccount, ProgramError> {
+    VestingAccount::try_deserialize(&mut &data[..])
+}
+
+/// The base state of a token account of either token program, such as a pool's vaults.
+/// Token-2022 extensions after the base are skipped.
+pub fn token_account(data: &[u8]) -> Result<TokenAccount, ProgramError> {
+    if data.len() < TokenAccount::LEN {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    TokenAccount::unpack(&data[..TokenAccount::LEN])
+}
@@ -0,0 +1,392 @@
+//! Instructions of the vesting program. The accounts a program can derive itself are derived
+//! here; the rest are arguments.
+
+use anchor_lang_vesting::{InstructionData, ToAccountMetas};
+use solana_program::instruction::{AccountMeta, Instruction};
+use solana_program::pubkey::Pubkey;
+use solana_program::{system_program, sysvar};
+use solana_vesting::staking::staking_program;
+use solana_vesting::{accounts, instruction, BatchGrant, TopUpPolicy, Tranche, VestingAccount, ID};
+
+use crate::pda::{find_investor_account, find_vault_account, find_vault_authority, find_vesting_account};
+use crate::staking::PoolKeys;
+
+/// The addresses of the investor account `owner` grants `mint` from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InvestorKeys {
+    pub owner: Pubkey,
+    pub mint: Pubkey,
+    pub token_program: Pubkey,
+    pub investor_account: Pubkey,
+    pub vault_authority: Pubkey,
+}
+
+impl InvestorKeys {
+    pub fn new(owner: Pubkey, mint: Pubkey, token_program: Pubkey) -> Self {
+        let investor_account = find_investor_account(&owner, &mint, &ID).0;
+        InvestorKeys {
+            owner,
+            mint,
+            token_program,
+            investor_account,
+            vault_authority: find_vault_authority(&investor_account, &ID).0,
+        }
+    }
+
+    /// The vesting account and vault of grant `grant_id`, paid out to `beneficiary_ata`
+    pub fn grant(&self, beneficiary_ata: &Pubkey, grant_id: u64) -> (Pubkey, Pubkey) {
+        let vesting_account = find_vesting_account(&self.investor_account, beneficiary_ata, grant_id, &ID).0;
+        (vesting_account, find_vault_account(&vesting_account, &ID).0)
+    }
+}
+
+/// The addresses of a grant its instructions take, read from the vesting account
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GrantKeys {
+    pub vesting_account: Pubkey,
+    pub vault_account: Pubkey,
+    pub vault_authority: Pubkey,
+    pub investor_account: Pubkey,
+    pub owner: Pubkey,
+    pub beneficiary: Pubkey,
+    pub mint: Pubkey,
+    pub token_program: Pubkey,
+}
+
+impl GrantKeys {
+    /// `token_program` is the owner of the grant's mint
+    pub fn new(vesting_account: Pubkey, state: &VestingAccount, token_program: Pubkey) -> Self {
+        GrantKeys {
+            vesting_account,
+            vault_account: find_vault_account(&vesting_account, &ID).0,
+            vault_authority: find_vault_authority(&state.investor_account, &ID).0,
+            investor_account: state.investor_account,
+            owner: state.owner,
+            beneficiary: state.beneficiary,
+            mint: state.mint,
+            token_program,
+        }
+    }
+}
+
+fn vesting_instruction(accounts: impl ToAccountMetas, data: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+pub fn initialize_vesting(keys: &InvestorKeys) -> Instruction {
+    vesting_instruction(
+        accounts::InitializeVesting {
+            investor_account: keys.investor_account,
+            mint: keys.mint,
+            owner: keys.owner,
+            system_program: system_program::ID,
+        },
+        instruction::InitializeVesting {},
+    )
+}
+
+fn initialize_accounts(
+    keys: &InvestorKeys,
+    beneficiary: &Pubkey,
+    beneficiary_ata: &Pubkey,
+    owner_token_account: &Pubkey,
+    grant_id: u64,
+) -> accounts::Initialize {
+    let (vesting_account, vault_account) = keys.grant(beneficiary_ata, grant_id);
+    accounts::Initialize {
+        owner: keys.owner,
+        beneficiary: *beneficiary,
+        mint: keys.mint,
+        beneficiary_ata: *beneficiary_ata,
+        vault_account,
+        vault_authority: keys.vault_authority,
+        owner_token_account: *owner_token_account,
+        vesting_account,
+        investor_account: keys.investor_account,
+        system_program: system_program::ID,
+        rent: sysvar::rent::ID,
+        token_program: keys.token_program,
+    }
+}
+
+/// Creates a periodic grant. `grant_id` is the investor account's `grant_count`.
+pub fn initialize(
+    keys: &InvestorKeys,
+    beneficiary: &Pubkey,
+    beneficiary_ata: &Pubkey,
+    owner_token_account: &Pubkey,
+    grant_id: u64,
+    grant: BatchGrant,
+) -> Instruction {
+    vesting_instruction(
+        initialize_accounts(keys, beneficiary, beneficiary_ata, owner_token_account, grant_id),
+        instruction::Initialize {
+            amount: grant.amount,
+            name: grant.name,
+            start_ts: grant.start_ts,
+            cliff_ts: grant.cliff_ts,
+            duration: grant.duration,
+            upfront_bps: grant.upfront_bps,
+            withdraw_period: grant.withdraw_period,
+            revocable: grant.revocable,
+        },
+    )
+}
+
+/// Creates a periodic grant for each beneficiary token account, numbered from `first_grant_id`
+pub fn initialize_batch(
+    keys: &InvestorKeys,
+    owner_token_account: &Pubkey,
+    first_grant_id: u64,
+    grants: Vec<(Pubkey, BatchGrant)>,
+) -> Instruction {
+    let mut accounts = accounts::InitializeBatch {
+        owner: keys.owner,
+        mint: keys.mint,
+        owner_token_account: *owner_token_account,
+        investor_account: keys.investor_account,
+        vault_authority: keys.vault_authority,
+        system_program: system_program::ID,
+        rent: sysvar::rent::ID,
+        token_program: keys.token_program,
+    }
+    .to_account_metas(None);
+
+    let mut batch = Vec::with_capacity(grants.len());
+    for (grant_id, (beneficiary_ata, grant)) in (first_grant_id..).zip(grants) {
+        let (vesting_account, vault_account) = keys.grant(&beneficiary_ata, grant_id);
+        accounts.push(AccountMeta::new_readonly(beneficiary_ata, false));
+        accounts.push(AccountMeta::new(vesting_account, false));
+        accounts.push(AccountMeta::new(vault_account, false));
+        batch.push(grant);
+    }
+
+    Instruction {
+        program_id: ID,
+        accounts,
+        data: instruction::InitializeBatch { grants: batch }.data(),
+    }
+}
+
+/// Creates a grant releasing `tranches`. `grant_id` is the investor account's `grant_count`.
+#[allow(clippy::too_many_arguments)]
+pub fn initialize_milestone(
+    keys: &InvestorKeys,
+    beneficiary: &Pubkey,
+    beneficiary_ata: &Pubkey,
+    owner_token_account: &Pubkey,
+    grant_id: u64,
+    amount: u64,
+    name: String,
+    tranches: Vec<Tranche>,
+    revocable: bool,
+) -> Instruction {
+    vesting_instruction(
+        initialize_accounts(keys, beneficiary, beneficiary_ata, owner_token_account, grant_id),
+        instruction::InitializeMilestone {
+            amount,
+            name,
+            tranches,
+            revocable,
+        },
+    )
+}
+
+pub fn add_token_to_vesting(keys: &GrantKeys, owner_token_account: &Pubkey, amount: u64, policy: TopUpPolicy) -> Instruction {
+    vesting_instruction(
+        accounts::AddTokenToVestingAccount {
+            owner: keys.owner,
+            vault_account: keys.vault_account,
+            vesting_account: keys.vesting_account,
+            mint: keys.mint,
+            owner_token_account: *owner_token_account,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+            token_program: keys.token_program,
+        },
+        instruction::AddTokenToVesting { amount, policy },
+    )
+}
+
+pub fn upfront(keys: &GrantKeys, beneficiary_ata: &Pubkey) -> Instruction {
+    vesting_instruction(
+        accounts::Upfront {
+            beneficiary: keys.beneficiary,
+            beneficiary_ata: *beneficiary_ata,
+            vault_account: keys.vault_account,
+            vesting_account: keys.vesting_account,
+            mint: keys.mint,
+            vault_authority: keys.vault_authority,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+            token_program: keys.token_program,
+        },
+        instruction::Upfront {},
+    )
+}
+
+pub fn withdraw(keys: &GrantKeys, beneficiary_ata: &Pubkey) -> Instruction {
+    vesting_instruction(
+        accounts::Withdraw {
+            beneficiary: keys.beneficiary,
+            beneficiary_ata: *beneficiary_ata,
+            vault_account: keys.vault_account,
+            vesting_account: keys.vesting_account,
+            mint: keys.mint,
+            vault_authority: keys.vault_authority,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+            token_program: keys.token_program,
+        },
+        instruction::Withdraw {},
+    )
+}
+
+pub fn enable_account(keys: &GrantKeys) -> Instruction {
+    vesting_instruction(
+        accounts::EnableAccount {
+            investor_account: keys.investor_account,
+            vesting_account: keys.vesting_account,
+            owner: keys.owner,
+            system_program: system_program::ID,
+        },
+        instruction::EnableAccount {},
+    )
+}
+
+pub fn disable_account(keys: &GrantKeys, shift_schedule: bool) -> Instruction {
+    vesting_instruction(
+        accounts::DisableAccount {
+            investor_account: keys.investor_account,
+            vesting_account: keys.vesting_account,
+            owner: keys.owner,
+            system_program: system_program::ID,
+        },
+        instruction::DisableAccount { shift_schedule },
+    )
+}
+
+pub fn rename_account(keys: &GrantKeys, name: String) -> Instruction {
+    vesting_instruction(
+        accounts::RenameAccount {
+            investor_account: keys.investor_account,
+            vesting_account: keys.vesting_account,
+            owner: keys.owner,
+            system_program: system_program::ID,
+        },
+        instruction::RenameAccount { name },
+    )
+}
+
+pub fn set_transfer_approval(keys: &GrantKeys, required: bool) -> Instruction {
+    vesting_instruction(
+        accounts::SetTransferApproval {
+            investor_account: keys.investor_account,
+            vesting_account: keys.vesting_account,
+            owner: keys.owner,
+        },
+        instruction::SetTransferApproval { required },
+    )
+}
+
+/// Moves the grant to `new_beneficiary`. The owner must co-sign when the grant requires
+/// their approval.
+pub fn transfer_beneficiary(
+    keys: &GrantKeys,
+    new_beneficiary: &Pubkey,
+    new_beneficiary_ata: &Pubkey,
+    owner_signs: bool,
+) -> Instruction {
+    let mut instruction = vesting_instruction(
+        accounts::TransferBeneficiary {
+            beneficiary: keys.beneficiary,
+            new_beneficiary: *new_beneficiary,
+            new_beneficiary_ata: *new_beneficiary_ata,
+            investor_account: keys.investor_account,
+            vesting_account: keys.vesting_account,
+            owner: keys.owner,
+        },
+        instruction::TransferBeneficiary {},
+    );
+    if owner_signs {
+        if let Some(owner) = instruction.accounts.iter_mut().find(|meta| meta.pubkey == keys.owner) {
+            owner.is_signer = true;
+        }
+    }
+
+    instruction
+}
+
+/// Revokes the grant, paying the vested part to `beneficiary_ata` and the rest back to
+/// `owner_token_account`
+pub fn revoke(keys: &GrantKeys, owner_token_account: &Pubkey, beneficiary_ata: &Pubkey) -> Instruction {
+    vesting_instruction(
+        accounts::Revoke {
+            owner: keys.owner,
+            vault_account: keys.vault_account,
+            vesting_account: keys.vesting_account,
+            mint: keys.mint,
+            owner_token_account: *owner_token_account,
+            beneficiary_ata: *beneficiary_ata,
+            vault_authority: keys.vault_authority,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+            token_program: keys.token_program,
+        },
+        instruction::Revoke {},
+    )
+}
+
+fn stake_vesting_accounts(keys: &GrantKeys, authority: &Pubkey, pool: &PoolKeys) -> accounts::StakeVesting {
+    accounts::StakeVesting {
+        authority: *authority,
+        vesting_account: keys.vesting_account,
+        vault_account: keys.vault_account,
+        mint: keys.mint,
+        vault_authority: keys.vault_authority,
+        beneficiary: keys.beneficiary,
+        staking_pool: pool.pool,
+        staking_vault: pool.staking_vault,
+        staking_reward_vault: pool.reward_vault,
+        staking_user: pool.user(&keys.beneficiary),
+        staking_pool_signer: pool.pool_signer,
+        staking_program: staking_program::ID,
+        token_program: keys.token_program,
+    }
+}
+
+/// Stakes `amount` of the unvested tokens in `pool`, signed by the beneficiary
+pub fn stake_vesting(keys: &GrantKeys, pool: &PoolKeys, amount: u64) -> Instruction {
+    vesting_instruction(
+        stake_vesting_accounts(keys, &keys.beneficiary, pool),
+        instruction::StakeVesting { amount },
+    )
+}
+
+/// Returns `amount` of the staked tokens to the vault, signed by `authority`, the beneficiary
+/// or the owner
+pub fn unstake_vesting(keys: &GrantKeys, authority: &Pubkey, pool: &PoolKeys, amount: u64) -> Instruction {
+    vesting_instruction(
+        stake_vesting_accounts(keys, authority, pool),
+        instruction::UnstakeVesting { amount },
+    )
+}
+
+pub fn close_vesting(keys: &GrantKeys) -> Instruction {
+    vesting_instruction(
+        accounts::CloseVesting {
+            owner: keys.owner,
+            investor_account: keys.investor_account,
+            vesting_account: keys.vesting_account,
+            vault_account: keys.vault_account,
+            mint: keys.mint,
+            vault_authority: keys.vault_authority,
+            token_program: keys.token_program,
+        },
+        instruction::CloseVesting {},
+    )
+}
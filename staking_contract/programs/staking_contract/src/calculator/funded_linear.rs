@@ -0,0 +1,30 @@
+use crate::calculator::*;
+
+/// Distributes the funded rewards linearly over `reward_duration`,
+/// at `reward_rate = funds / reward_duration` as set by `fund`.
+pub struct FundedLinearRewardCalculator;
+
+impl RewardCalculator for FundedLinearRewardCalculator {
+    fn reward_emission(
+        &self,
+        pool: &PoolRewards,
+        _total_staked: u64,
+        last_time_reward_applicable: u64,
+    ) -> U192 {
+        U192::from(last_time_reward_applicable.saturating_sub(pool.last_update_time))
+            .checked_mul(pool.reward_rate.into())
+            .unwrap()
+    }
+
+    fn emission_rate(&self, pool: &PoolRewards, _total_staked: u64, now: u64) -> u64 {
+        if now < pool.reward_duration_end {
+            pool.reward_rate
+        } else {
+            0
+        }
+    }
+
+    fn last_time_reward_applicable(&self, pool: &PoolRewards, now: u64) -> u64 {
+        now.min(pool.reward_duration_end)
+    }
+}
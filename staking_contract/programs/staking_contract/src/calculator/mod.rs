@@ -1,26 +1,215 @@
 use crate::*;
+use funded_linear::*;
 use pool::*;
+use spl_math::uint::U192;
+use target_apr::*;
 
+mod funded_linear;
 mod pool;
+mod target_apr;
+
+/// The pool state the reward math reads, as plain data. Off-chain code can build it from a
+/// fetched `Pool` and the balance of its reward vault, and preview rewards with the same math
+/// the program runs.
+#[derive(Clone, Debug)]
+pub struct PoolRewards {
+    pub calculator_kind: CalculatorKind,
+    pub reward_rate: u64,
+    pub reward_duration_end: u64,
+    pub last_update_time: u64,
+    pub target_apr_bps: u64,
+    pub max_emission_rate: u64,
+    pub reward_undistributed: u64,
+    pub reward_owed_total: u64,
+    pub reward_per_token_stored: u128,
+    pub emission_phases: Vec<EmissionPhase>,
+    pub reward_vault_balance: u64,
+}
+
+/// A staked balance of a user or merchant, as the reward math reads it
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Position {
+    pub balance_staked: u64,
+    pub reward_per_token_complete: u128,
+    pub reward_per_token_pending: u64,
+}
+
+impl Pool {
+    pub fn rewards(&self, reward_vault_balance: u64) -> PoolRewards {
+        PoolRewards {
+            calculator_kind: self.calculator_kind,
+            reward_rate: self.reward_rate,
+            reward_duration_end: self.reward_duration_end,
+            last_update_time: self.last_update_time,
+            target_apr_bps: self.target_apr_bps,
+            max_emission_rate: self.max_emission_rate,
+            reward_undistributed: self.reward_undistributed,
+            reward_owed_total: self.reward_owed_total,
+            reward_per_token_stored: self.reward_per_token_stored,
+            emission_phases: self.emission_phases.clone(),
+            reward_vault_balance,
+        }
+    }
+}
+
+impl User {
+    pub fn position(&self) -> Position {
+        Position {
+            balance_staked: self.balance_staked,
+            reward_per_token_complete: self.reward_per_token_complete,
+            reward_per_token_pending: self.reward_per_token_pending,
+        }
+    }
+}
+
+impl Merchant {
+    pub fn position(&self) -> Position {
+        Position {
+            balance_staked: self.balance_staked,
+            reward_per_token_complete: self.reward_per_token_complete,
+            reward_per_token_pending: self.reward_per_token_pending,
+        }
+    }
+}
+
+impl PoolRewards {
+    /// Accrues the reward per token up to `now`
+    pub fn accrue(&mut self, total_staked: u64, now: u64) {
+        let calc = get_calculator(self.calculator_kind);
+        let last_time_reward_applicable = calc.last_time_reward_applicable(self, now);
+        let reward = calc.reward_per_token(self, total_staked, last_time_reward_applicable);
+
+        if total_staked > 0 {
+            let emission = calc
+                .reward_emission(self, total_staked, last_time_reward_applicable)
+                .try_into()
+                .unwrap_or(u64::MAX);
+            self.reward_undistributed = self.reward_undistributed.saturating_sub(emission);
+            self.reward_owed_total = self.reward_owed_total.saturating_add(emission);
+        }
+
+        self.reward_per_token_stored = reward;
+        self.last_update_time = last_time_reward_applicable;
+    }
+
+    /// The reward per token the pool has accrued by `now`
+    pub fn reward_per_token(&self, total_staked: u64, now: u64) -> u128 {
+        let calc = get_calculator(self.calculator_kind);
+        let last_time_reward_applicable = calc.last_time_reward_applicable(self, now);
+        calc.reward_per_token(self, total_staked, last_time_reward_applicable)
+    }
+
+    /// What the reward vault holds beyond the rewards already owed to stakers
+    pub fn reward_available(&self) -> u64 {
+        self.reward_vault_balance.saturating_sub(self.reward_owed_total)
+    }
+
+    /// The reward a user has pending at `now`
+    pub fn user_earned_amount(&self, user: &Position, total_staked: u64, now: u64) -> u64 {
+        self.earned_amount(user, total_staked, now)
+    }
+
+    /// The reward a merchant has pending at `now`
+    pub fn merchant_earned_amount(&self, merchant: &Position, total_staked: u64, now: u64) -> u64 {
+        self.earned_amount(merchant, total_staked, now)
+    }
+
+    /// The pool's reward rate at `now` annualised, in basis points of the total staked
+    pub fn effective_apr_bps(&self, total_staked: u64, now: u64) -> u64 {
+        get_calculator(self.calculator_kind).effective_apr_bps(self, total_staked, now)
+    }
+
+    fn earned_amount(&self, position: &Position, total_staked: u64, now: u64) -> u64 {
+        let mut accrued = self.clone();
+        accrued.accrue(total_staked, now);
+        get_calculator(self.calculator_kind).earned_amount(&accrued, position)
+    }
+}
 
 /// Retrieve a calculator for a specific pool
-pub fn get_calculator() -> Box<dyn RewardCalculator> {
-    Box::new(PoolRewardCalculator)
+pub fn get_calculator(kind: CalculatorKind) -> Box<dyn RewardCalculator> {
+    match kind {
+        CalculatorKind::FixedRate => Box::new(PoolRewardCalculator),
+        CalculatorKind::FundedLinear => Box::new(FundedLinearRewardCalculator),
+        CalculatorKind::TargetApr => Box::new(TargetAprRewardCalculator),
+    }
 }
 
 /// A reward calculator handles the calculations of reward rates and user reward amounts
 pub trait RewardCalculator {
+    /// Calculates the rewards the whole pool distributes between the last update and
+    /// `last_time_reward_applicable`
+    fn reward_emission(
+        &self,
+        pool: &PoolRewards,
+        total_staked: u64,
+        last_time_reward_applicable: u64,
+    ) -> U192;
+
+    /// The rewards the whole pool currently distributes per second
+    fn emission_rate(&self, pool: &PoolRewards, total_staked: u64, now: u64) -> u64;
+
+    /// The current reward rate annualised, in basis points of the total staked
+    fn effective_apr_bps(&self, pool: &PoolRewards, total_staked: u64, now: u64) -> u64 {
+        if total_staked == 0 {
+            return 0;
+        }
+
+        (self.emission_rate(pool, total_staked, now) as u128)
+            .checked_mul(SECONDS_PER_YEAR.into())
+            .unwrap()
+            .checked_mul(BPS_DENOMINATOR.into())
+            .unwrap()
+            .checked_div(total_staked.into())
+            .unwrap()
+            .try_into()
+            .unwrap_or(u64::MAX)
+    }
+
+    /// The latest timestamp rewards accrue up to
+    fn last_time_reward_applicable(&self, _pool: &PoolRewards, now: u64) -> u64 {
+        now
+    }
+
     /// Calculates the current reward per token that should have been paid out
     fn reward_per_token(
         &self,
-        pool: &Account<Pool>,
+        pool: &PoolRewards,
         total_staked: u64,
         last_time_reward_applicable: u64,
-    ) -> u128;
+    ) -> u128 {
+        if total_staked == 0 {
+            return pool.reward_per_token_stored;
+        }
 
-    /// Calculates the amount that a user earned
-    fn user_earned_amount(&self, pool: &Account<Pool>, user: &Account<User>) -> u64;
+        let emission = self.reward_emission(pool, total_staked, last_time_reward_applicable);
+        pool.reward_per_token_stored
+            .checked_add(
+                emission
+                    .checked_mul(PRECISION.into())
+                    .unwrap()
+                    .checked_div(total_staked.into())
+                    .unwrap()
+                    .try_into()
+                    .unwrap(), //back to u128
+            )
+            .unwrap()
+    }
 
-    /// Calculates the amount that a merchant earned
-    fn merchant_earned_amount(&self, pool: &Account<Pool>, user: &Account<Merchant>) -> u64;
+    /// Calculates the amount that a user or merchant earned up to the pool's last update
+    fn earned_amount(&self, pool: &PoolRewards, position: &Position) -> u64 {
+        (position.balance_staked as u128)
+            .checked_mul(
+                pool.reward_per_token_stored
+                    .checked_sub(position.reward_per_token_complete)
+                    .unwrap(),
+            )
+            .unwrap()
+            .checked_div(PRECISION)
+            .unwrap()
+            .checked_add(position.reward_per_token_pending as u128)
+            .unwrap()
+            .try_into()
+            .unwrap() //back to u64
+    }
 }
@@ -1,81 +1,59 @@
 use crate::calculator::*;
-use spl_math::uint::U192;
 
+/// Emits a fixed number of tokens per second, following the pool's emission phases
+/// once any are scheduled. `REWARD_RATE` only applies until the first phase is added:
+/// from then on the pool emits nothing outside its phases, between them or after the last
+/// one ends, until another phase is added.
 pub struct PoolRewardCalculator;
 
 impl RewardCalculator for PoolRewardCalculator {
-    fn reward_per_token(
+    fn reward_emission(
         &self,
-        pool: &Account<Pool>,
-        total_staked: u64,
+        pool: &PoolRewards,
+        _total_staked: u64,
         last_time_reward_applicable: u64,
-    ) -> u128 {
-        if total_staked == 0 {
-            return pool.reward_per_token_stored;
+    ) -> U192 {
+        if pool.emission_phases.is_empty() {
+            return U192::from(last_time_reward_applicable)
+                .checked_sub(pool.last_update_time.into())
+                .unwrap()
+                .checked_mul(REWARD_RATE.into())
+                .unwrap();
         }
 
-        let time_period = U192::from(last_time_reward_applicable)
-            .checked_sub(pool.last_update_time.into())
-            .unwrap();
-        let reward = pool
-            .reward_per_token_stored
-            .checked_add(
-                time_period
-                    .checked_mul(REWARD_RATE.into())
-                    .unwrap()
-                    .checked_mul(PRECISION.into())
-                    .unwrap()
-                    .checked_div(total_staked.into())
-                    .unwrap()
-                    .try_into()
-                    .unwrap(), //back to u128
-            )
-            .unwrap();
-
-        reward
+        phase_emission(pool, pool.last_update_time, last_time_reward_applicable)
     }
 
-    fn user_earned_amount(
-        &self,
-        pool: &anchor_lang::Account<Pool>,
-        user: &anchor_lang::Account<User>,
-    ) -> u64 {
-        let user_reward: u64 = (user.balance_staked as u128)
-            .checked_mul(
-                (pool.reward_per_token_stored as u128)
-                    .checked_sub(user.reward_per_token_complete as u128)
-                    .unwrap(),
-            )
-            .unwrap()
-            .checked_div(PRECISION)
-            .unwrap()
-            .checked_add(user.reward_per_token_pending as u128)
-            .unwrap()
-            .try_into()
-            .unwrap(); //back to u64
+    fn emission_rate(&self, pool: &PoolRewards, _total_staked: u64, now: u64) -> u64 {
+        if pool.emission_phases.is_empty() {
+            return REWARD_RATE;
+        }
 
-        user_reward
+        pool.emission_phases
+            .iter()
+            .find(|phase| phase.start_ts <= now && now < phase.end_ts)
+            .map_or(0, |phase| phase.reward_rate)
     }
+}
 
-    fn merchant_earned_amount(
-        &self,
-        pool: &anchor_lang::Account<Pool>,
-        merchant: &anchor_lang::Account<Merchant>,
-    ) -> u64 {
-        let merchant_reward: u64 = (merchant.balance_staked as u128)
-            .checked_mul(
-                (pool.reward_per_token_stored as u128)
-                    .checked_sub(merchant.reward_per_token_complete as u128)
-                    .unwrap(),
-            )
-            .unwrap()
-            .checked_div(PRECISION)
-            .unwrap()
-            .checked_add(merchant.reward_per_token_pending as u128)
-            .unwrap()
-            .try_into()
-            .unwrap(); //back to u64
-
-        merchant_reward
-    }
+/// Total rewards the pool's emission phases distribute between `from` and `to`,
+/// integrating each phase's rate over the part of the interval it covers
+fn phase_emission(pool: &PoolRewards, from: u64, to: u64) -> U192 {
+    pool.emission_phases
+        .iter()
+        .fold(U192::zero(), |emission, phase| {
+            let start = phase.start_ts.max(from);
+            let end = phase.end_ts.min(to);
+            if end <= start {
+                return emission;
+            }
+
+            emission
+                .checked_add(
+                    U192::from(end - start)
+                        .checked_mul(phase.reward_rate.into())
+                        .unwrap(),
+                )
+                .unwrap()
+        })
 }
@@ -0,0 +1,72 @@
+use crate::calculator::*;
+
+/// Pays stakers a fixed annual percentage of the total staked amount, emitting no more than
+/// `max_emission_rate` per second and no more than the reward vault holds beyond what is
+/// already owed.
+pub struct TargetAprRewardCalculator;
+
+impl RewardCalculator for TargetAprRewardCalculator {
+    fn reward_emission(
+        &self,
+        pool: &PoolRewards,
+        total_staked: u64,
+        last_time_reward_applicable: u64,
+    ) -> U192 {
+        let time_period = U192::from(last_time_reward_applicable)
+            .checked_sub(pool.last_update_time.into())
+            .unwrap();
+
+        let target = time_period
+            .checked_mul(total_staked.into())
+            .unwrap()
+            .checked_mul(pool.target_apr_bps.into())
+            .unwrap()
+            .checked_div(BPS_DENOMINATOR.into())
+            .unwrap()
+            .checked_div(SECONDS_PER_YEAR.into())
+            .unwrap();
+        let cap = time_period
+            .checked_mul(pool.max_emission_rate.into())
+            .unwrap();
+
+        target.min(cap).min(pool.reward_available().into())
+    }
+
+    fn emission_rate(&self, pool: &PoolRewards, total_staked: u64, _now: u64) -> u64 {
+        if pool.reward_available() == 0 {
+            return 0;
+        }
+
+        let target: u64 = (total_staked as u128)
+            .checked_mul(pool.target_apr_bps.into())
+            .unwrap()
+            .checked_div(BPS_DENOMINATOR.into())
+            .unwrap()
+            .checked_div(SECONDS_PER_YEAR.into())
+            .unwrap()
+            .try_into()
+            .unwrap(); //back to u64
+
+        target.min(pool.max_emission_rate)
+    }
+
+    /// The target APR, lowered to what `max_emission_rate` pays on `total_staked`. Computed
+    /// from the APR itself, as the per-second emission of a realistic stake rounds down to 0.
+    fn effective_apr_bps(&self, pool: &PoolRewards, total_staked: u64, _now: u64) -> u64 {
+        if total_staked == 0 || pool.reward_available() == 0 {
+            return 0;
+        }
+
+        let cap: u64 = (pool.max_emission_rate as u128)
+            .checked_mul(SECONDS_PER_YEAR.into())
+            .unwrap()
+            .checked_mul(BPS_DENOMINATOR.into())
+            .unwrap()
+            .checked_div(total_staked.into())
+            .unwrap()
+            .try_into()
+            .unwrap_or(u64::MAX);
+
+        pool.target_apr_bps.min(cap)
+    }
+}
@@ -4,10 +4,14 @@ use std::fmt::Debug;
 
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::{clock, sysvar};
-use anchor_spl::token::{self, Mint, Token, TokenAccount};
+use anchor_spl::token::{CloseAccount, InitializeAccount, Token};
 
 use crate::calculator::*;
-mod calculator;
+use crate::token_interface::{token_account, TransferChecked};
+use crate::voting::*;
+pub mod calculator;
+mod token_interface;
+mod voting;
 
 declare_id!("6RwUKAHuSbadG6sQzcfEYKh6UGvPvCXB1nq7BPEyn5Jg");
 
@@ -15,12 +19,63 @@ const MIN_DURATION: u64 = 1;
 const MERCHANT_PDA_SEED: &[u8] = b"merchant-pool";
 const STAKING_VAULT_PDA_SEED: &[u8] = b"staking-vault";
 const REWARD_VAULT_PDA_SEED: &[u8] = b"reward-vault";
+const VOTER_WEIGHT_RECORD_PDA_SEED: &[u8] = b"voter-weight-record";
 // const LOCKING_PERIOD_OF_STAKING_ON_BEHALF: i64 = 2 * 365 * 86400;
 const LOCKING_PERIOD_OF_STAKING_ON_BEHALF: i64 = 1;
 
 const PRECISION: u128 = u64::MAX as u128;
 const REWARD_RATE: u64 = 10_000_000;
+const MAX_EMISSION_PHASES: usize = 16;
 
+const BPS_DENOMINATOR: u64 = 10_000;
+const SECONDS_PER_YEAR: u64 = 365 * 86400;
+// Locking for this long (or longer) doubles a position's voting power
+const MAX_VOTING_LOCK_PERIOD: i64 = 4 * 365 * 86400;
+const MAX_LOCK_BONUS_BPS: u64 = 10_000;
+
+/// The PDA that owns the vaults of `pool`
+pub fn find_pool_signer(pool: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[pool.as_ref()], program_id)
+}
+
+/// The vault holding the staked tokens of the pool created with `pool_token_ata`
+pub fn find_staking_vault(pool_token_ata: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[STAKING_VAULT_PDA_SEED, pool_token_ata.as_ref()], program_id)
+}
+
+/// The vault holding the rewards of the pool created with `pool_token_ata`
+pub fn find_reward_vault(pool_token_ata: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[REWARD_VAULT_PDA_SEED, pool_token_ata.as_ref()], program_id)
+}
+
+/// The user account of `owner` in `pool`
+pub fn find_user(owner: &Pubkey, pool: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[owner.as_ref(), pool.as_ref()], program_id)
+}
+
+/// The merchant account of `owner` in `pool`
+pub fn find_merchant(owner: &Pubkey, pool: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[MERCHANT_PDA_SEED, owner.as_ref(), pool.as_ref()], program_id)
+}
+
+/// The account of `owner` staking to `merchant` in `pool`
+pub fn find_merchant_user(owner: &Pubkey, merchant: &Pubkey, pool: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[owner.as_ref(), merchant.as_ref(), pool.as_ref()], program_id)
+}
+
+/// The voter weight record of `owner` in `pool`
+pub fn find_voter_weight_record(pool: &Pubkey, owner: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[VOTER_WEIGHT_RECORD_PDA_SEED, pool.as_ref(), owner.as_ref()], program_id)
+}
+
+
+/// The current unix timestamp, as the reward math counts time
+fn current_timestamp() -> Result<u64> {
+    clock::Clock::get()?
+        .unix_timestamp
+        .try_into()
+        .map_err(|_| ErrorCode::InvalidTimestamp.into())
+}
 
 /// Update the pool with the total reward per token
 pub fn update_rewards(
@@ -28,25 +83,27 @@ pub fn update_rewards(
     user: Option<&mut Box<Account<User>>>,
     merchant: Option<&mut Box<Account<Merchant>>>,
     total_staked: u64,
+    reward_vault_balance: u64,
 ) -> Result<()> {
-    let last_time_reward_applicable = clock::Clock::get().unwrap().unix_timestamp.try_into().unwrap();
+    let now = clock::Clock::get().unwrap().unix_timestamp.try_into().unwrap();
 
-    let calc = get_calculator();
-    let reward =
-        calc.reward_per_token(pool, total_staked, last_time_reward_applicable);
-
-    pool.reward_per_token_stored = reward;
-    pool.last_update_time = last_time_reward_applicable;
+    let mut rewards = pool.rewards(reward_vault_balance);
+    rewards.accrue(total_staked, now);
+    pool.reward_per_token_stored = rewards.reward_per_token_stored;
+    pool.last_update_time = rewards.last_update_time;
+    pool.reward_undistributed = rewards.reward_undistributed;
+    pool.reward_owed_total = rewards.reward_owed_total;
 
+    let calc = get_calculator(pool.calculator_kind);
     if let Some(u) = user {
-        let user_reward = calc.user_earned_amount(pool, u);
+        let user_reward = calc.earned_amount(&rewards, &u.position());
 
         u.reward_per_token_pending = user_reward;
         u.reward_per_token_complete = pool.reward_per_token_stored;
     }
 
     if let Some(m) = merchant {
-        let merchant_reward = calc.merchant_earned_amount(pool, m);
+        let merchant_reward = calc.earned_amount(&rewards, &m.position());
 
         m.reward_per_token_pending = merchant_reward;
         m.reward_per_token_complete = pool.reward_per_token_stored;
@@ -55,6 +112,30 @@ pub fn update_rewards(
     Ok(())
 }
 
+/// Splits a pending reward into what the reward vault can pay now and what stays owed.
+/// The claimant's unpaid remainder replaces their previous share of the pool's reward debt.
+pub fn settle_reward_claim(
+    pool: &mut Pool,
+    claimant_debt: &mut u64,
+    pending: u64,
+    vault_balance: u64,
+) -> (u64, u64) {
+    let paid = pending.min(vault_balance);
+    let unpaid = pending - paid;
+
+    pool.reward_owed_total = pool.reward_owed_total.saturating_sub(paid);
+
+    pool.reward_debt_total = pool
+        .reward_debt_total
+        .checked_sub(*claimant_debt)
+        .unwrap()
+        .checked_add(unpaid)
+        .unwrap();
+    *claimant_debt = unpaid;
+
+    (paid, unpaid)
+}
+
 #[program]
 pub mod staking_contract {
     use super::*;
@@ -63,11 +144,40 @@ pub mod staking_contract {
         ctx: Context<InitializeMainPool>,
         pool_nonce: u8,
         reward_duration: u64,
+        calculator_kind: CalculatorKind,
+        target_apr_bps: u64,
+        max_emission_rate: u64,
     ) -> ProgramResult {
         if reward_duration < MIN_DURATION {
             return Err(ErrorCode::DurationTooShort.into());
         }
 
+        if calculator_kind == CalculatorKind::TargetApr && (target_apr_bps == 0 || max_emission_rate == 0) {
+            return Err(ErrorCode::InvalidTargetApr.into());
+        }
+
+        let pool_token_ata = ctx.accounts.pool_token_ata.key();
+        let (_, staking_vault_bump) = Pubkey::find_program_address(
+            &[STAKING_VAULT_PDA_SEED, pool_token_ata.as_ref()],
+            ctx.program_id,
+        );
+        ctx.accounts.create_vault(
+            &ctx.accounts.staking_vault,
+            &ctx.accounts.staking_mint,
+            &ctx.accounts.staking_token_program,
+            &[STAKING_VAULT_PDA_SEED, pool_token_ata.as_ref(), &[staking_vault_bump]],
+        )?;
+        let (_, reward_vault_bump) = Pubkey::find_program_address(
+            &[REWARD_VAULT_PDA_SEED, pool_token_ata.as_ref()],
+            ctx.program_id,
+        );
+        ctx.accounts.create_vault(
+            &ctx.accounts.reward_vault,
+            &ctx.accounts.reward_mint,
+            &ctx.accounts.reward_token_program,
+            &[REWARD_VAULT_PDA_SEED, pool_token_ata.as_ref(), &[reward_vault_bump]],
+        )?;
+
         let pool = &mut ctx.accounts.pool;
 
         pool.authority = ctx.accounts.authority.key();
@@ -77,12 +187,19 @@ pub mod staking_contract {
         pool.staking_vault = ctx.accounts.staking_vault.key();
         pool.reward_mint = ctx.accounts.reward_mint.key();
         pool.reward_vault = ctx.accounts.reward_vault.key();
+        pool.staking_token_program = ctx.accounts.staking_token_program.key();
+        pool.reward_token_program = ctx.accounts.reward_token_program.key();
         pool.reward_duration = reward_duration;
         pool.reward_duration_end = 0;
         pool.last_update_time = 0;
 
         pool.reward_rate = 0;
         pool.reward_per_token_stored = 0;
+        pool.calculator_kind = calculator_kind;
+        pool.target_apr_bps = target_apr_bps;
+        pool.max_emission_rate = max_emission_rate;
+        pool.reward_undistributed = 0;
+        pool.reward_debt_total = 0;
 
         pool.user_stake_count = 0;
         pool.merchant_count = 0;
@@ -98,9 +215,11 @@ pub mod staking_contract {
 
         user.reward_per_token_complete = 0;
         user.reward_per_token_pending = 0;
+        user.reward_debt = 0;
 
         user.balance_staked = 0;
         user.balance_self_staked = 0;
+        user.balance_locked = 0;
         user.registerd_at = current_ts;
         user.staked_count = 0;
         user.claimed_count = 0;
@@ -129,6 +248,86 @@ pub mod staking_contract {
         Ok(())
     }
 
+    /// Retunes the target APR and emission cap of a target-APR pool.
+    /// Rewards up to now are settled at the previous rate.
+    pub fn set_target_apr(
+        ctx: Context<SetTargetApr>,
+        target_apr_bps: u64,
+        max_emission_rate: u64,
+    ) -> Result<()> {
+        if ctx.accounts.pool.calculator_kind != CalculatorKind::TargetApr
+            || target_apr_bps == 0
+            || max_emission_rate == 0
+        {
+            return Err(ErrorCode::InvalidTargetApr.into());
+        }
+
+        let total_staked = token_account(&ctx.accounts.staking_vault)?.amount;
+        let reward_vault_balance = token_account(&ctx.accounts.reward_vault)?.amount;
+        let pool = &mut ctx.accounts.pool;
+        update_rewards(pool, None, None, total_staked, reward_vault_balance).unwrap();
+
+        pool.target_apr_bps = target_apr_bps;
+        pool.max_emission_rate = max_emission_rate;
+
+        Ok(())
+    }
+
+    /// Sets the governance realm voter weight records of this pool are created for.
+    pub fn set_realm(ctx: Context<SetRealm>, realm: Pubkey) -> Result<()> {
+        ctx.accounts.pool.realm = realm;
+
+        Ok(())
+    }
+
+    /// Appends a future reward emission phase. Phases can only be scheduled after the
+    /// previous one ends and never in the past, so rewards already accrued stay unchanged.
+    /// Phases that have ended are dropped, so only the ones still running or to come count
+    /// towards `MAX_EMISSION_PHASES`.
+    pub fn add_emission_phase(
+        ctx: Context<AddEmissionPhase>,
+        start_ts: u64,
+        end_ts: u64,
+        reward_rate: u64,
+    ) -> Result<()> {
+        if end_ts <= start_ts {
+            return Err(ErrorCode::InvalidEmissionPhase.into());
+        }
+
+        let now: u64 = clock::Clock::get().unwrap().unix_timestamp.try_into().unwrap();
+        if start_ts < now {
+            return Err(ErrorCode::EmissionPhaseInPast.into());
+        }
+
+        let total_staked = token_account(&ctx.accounts.staking_vault)?.amount;
+        let reward_vault_balance = token_account(&ctx.accounts.reward_vault)?.amount;
+        let pool = &mut ctx.accounts.pool;
+
+        if let Some(last) = pool.emission_phases.last() {
+            if start_ts < last.end_ts {
+                return Err(ErrorCode::EmissionPhaseOverlap.into());
+            }
+        }
+
+        // Settle what accrued under the current schedule before changing it
+        update_rewards(pool, None, None, total_staked, reward_vault_balance).unwrap();
+
+        // Ended phases have nothing left to emit once the pool is settled
+        pool.emission_phases.retain(|phase| phase.end_ts > now);
+        if pool.emission_phases.len() >= MAX_EMISSION_PHASES {
+            return Err(ErrorCode::MaxEmissionPhases.into());
+        }
+
+        pool.emission_phases.push(EmissionPhase {
+            start_ts,
+            end_ts,
+            reward_rate,
+        });
+        pool.reward_duration_end = end_ts;
+
+        Ok(())
+    }
+
     /// A user stakes tokens in the main pool.
     pub fn stake(
         ctx: Context<Stake>,
@@ -146,9 +345,10 @@ pub mod staking_contract {
             return Err(ErrorCode::PoolPaused.into());
         }
 
-        let total_staked = ctx.accounts.staking_vault.amount;
+        let total_staked = token_account(&ctx.accounts.staking_vault)?.amount;
+        let reward_vault_balance = token_account(&ctx.accounts.reward_vault)?.amount;
         let user_opt = Some(&mut ctx.accounts.user);
-        update_rewards(pool, user_opt, None, total_staked).unwrap();
+        update_rewards(pool, user_opt, None, total_staked, reward_vault_balance).unwrap();
 
         if ctx.accounts.user.staked_count == 0 {
             ctx.accounts.user.claimed_ts = current_ts;
@@ -171,17 +371,37 @@ pub mod staking_contract {
             ctx.accounts.user.claimed_ts = current_ts;
         }
 
+        // Transfer tokens into the stake vault.
+        {
+            let decimals = token_interface::mint(&ctx.accounts.staking_mint)?.decimals;
+            let cpi_ctx = CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.stake_from_account.to_account_info(),
+                    mint: ctx.accounts.staking_mint.to_account_info(),
+                    to: ctx.accounts.staking_vault.to_account_info(),
+                    authority: ctx.accounts.owner.to_account_info(), //todo use user account as signer
+                },
+            );
+            token_interface::transfer_checked(cpi_ctx, amount, decimals)?;
+        }
+        // Credit what reached the vault, net of any transfer fee.
+        let received = token_account(&ctx.accounts.staking_vault)?
+            .amount
+            .checked_sub(total_staked)
+            .unwrap();
+
         ctx.accounts.user.balance_staked = ctx
             .accounts
             .user
             .balance_staked
-            .checked_add(amount)
+            .checked_add(received)
             .unwrap();
         ctx.accounts.user.balance_self_staked = ctx
             .accounts
             .user
             .balance_self_staked
-            .checked_add(amount)
+            .checked_add(received)
             .unwrap();
         ctx.accounts.user.staked_ts = current_ts;
         ctx.accounts.user.staked_count = ctx
@@ -190,19 +410,6 @@ pub mod staking_contract {
             .staked_count
             .checked_add(1)
             .unwrap();
-            
-        // Transfer tokens into the stake vault.
-        {
-            let cpi_ctx = CpiContext::new(
-                ctx.accounts.token_program.to_account_info(),
-                token::Transfer {
-                    from: ctx.accounts.stake_from_account.to_account_info(),
-                    to: ctx.accounts.staking_vault.to_account_info(),
-                    authority: ctx.accounts.owner.to_account_info(), //todo use user account as signer
-                },
-            );
-            token::transfer(cpi_ctx, amount)?;
-        }
 
         Ok(())
     }
@@ -223,25 +430,28 @@ pub mod staking_contract {
             return Err(ErrorCode::InsufficientFundUnstake.into());
         }
 
-        let total_staked = ctx.accounts.staking_vault.amount;
+        let total_staked = token_account(&ctx.accounts.staking_vault)?.amount;
+        let reward_vault_balance = token_account(&ctx.accounts.reward_vault)?.amount;
         let user_opt = Some(&mut ctx.accounts.user);
-        update_rewards(pool, user_opt, None, total_staked).unwrap();
+        update_rewards(pool, user_opt, None, total_staked, reward_vault_balance).unwrap();
 
         // Transfer tokens from the pool vault to user vault.
         {
+            let decimals = token_interface::mint(&ctx.accounts.staking_mint)?.decimals;
             let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
             let pool_signer = &[&seeds[..]];
 
             let cpi_ctx = CpiContext::new_with_signer(
                 ctx.accounts.token_program.to_account_info(),
-                token::Transfer {
+                TransferChecked {
                     from: ctx.accounts.staking_vault.to_account_info(),
+                    mint: ctx.accounts.staking_mint.to_account_info(),
                     to: ctx.accounts.stake_from_account.to_account_info(),
                     authority: ctx.accounts.pool_signer.to_account_info(),
                 },
                 pool_signer,
             );
-            token::transfer(cpi_ctx, spt_amount)?;
+            token_interface::transfer_checked(cpi_ctx, spt_amount, decimals)?;
         }
 
         ctx.accounts.user.balance_staked = ctx
@@ -261,32 +471,195 @@ pub mod staking_contract {
         Ok(())
     }
 
+    /// Stakes tokens held by `lock_authority` (e.g. a vesting program's vault) for a user.
+    /// The position earns rewards for the user but only the lock authority can unstake it.
+    /// The user has to sign, so no one else can claim their account as lock authority.
+    pub fn stake_locked(ctx: Context<StakeLocked>, amount: u64) -> Result<()> {
+        if amount == 0 {
+            return Err(ErrorCode::AmountMustBeGreaterThanZero.into());
+        }
+
+        if !ctx.accounts.owner.is_signer {
+            return Err(ErrorCode::OwnerMustSign.into());
+        }
+
+        if ctx.accounts.pool.paused {
+            return Err(ErrorCode::PoolPaused.into());
+        }
+
+        let lock_authority = ctx.accounts.lock_authority.key();
+        if ctx.accounts.user.balance_locked == 0 {
+            ctx.accounts.user.lock_authority = lock_authority;
+        } else if ctx.accounts.user.lock_authority != lock_authority {
+            return Err(ErrorCode::LockAuthorityMismatch.into());
+        }
+
+        let total_staked = token_account(&ctx.accounts.staking_vault)?.amount;
+        let reward_vault_balance = token_account(&ctx.accounts.reward_vault)?.amount;
+        let pool = &mut ctx.accounts.pool;
+        let user_opt = Some(&mut ctx.accounts.user);
+        update_rewards(pool, user_opt, None, total_staked, reward_vault_balance).unwrap();
+
+        let now = clock::Clock::get().unwrap().unix_timestamp;
+        if ctx.accounts.user.balance_staked == 0 {
+            ctx.accounts.user.claimed_ts = now;
+        }
+
+        // Transfer tokens into the stake vault.
+        {
+            let decimals = token_interface::mint(&ctx.accounts.staking_mint)?.decimals;
+            let cpi_ctx = CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.locked_token_account.to_account_info(),
+                    mint: ctx.accounts.staking_mint.to_account_info(),
+                    to: ctx.accounts.staking_vault.to_account_info(),
+                    authority: ctx.accounts.lock_authority.to_account_info(),
+                },
+            );
+            token_interface::transfer_checked(cpi_ctx, amount, decimals)?;
+        }
+        // Credit what reached the vault, net of any transfer fee.
+        let received = token_account(&ctx.accounts.staking_vault)?
+            .amount
+            .checked_sub(total_staked)
+            .unwrap();
+
+        ctx.accounts.user.balance_staked = ctx
+            .accounts
+            .user
+            .balance_staked
+            .checked_add(received)
+            .unwrap();
+        ctx.accounts.user.balance_locked = ctx
+            .accounts
+            .user
+            .balance_locked
+            .checked_add(received)
+            .unwrap();
+        ctx.accounts.user.staked_ts = now;
+
+        Ok(())
+    }
+
+    /// Returns locked tokens to an account of the lock authority.
+    pub fn unstake_locked(ctx: Context<StakeLocked>, amount: u64) -> Result<()> {
+        if amount == 0 {
+            return Err(ErrorCode::AmountMustBeGreaterThanZero.into());
+        }
+
+        if ctx.accounts.user.lock_authority != ctx.accounts.lock_authority.key() {
+            return Err(ErrorCode::LockAuthorityMismatch.into());
+        }
+
+        if ctx.accounts.user.balance_locked < amount {
+            return Err(ErrorCode::InsufficientFundUnstake.into());
+        }
+
+        let total_staked = token_account(&ctx.accounts.staking_vault)?.amount;
+        let reward_vault_balance = token_account(&ctx.accounts.reward_vault)?.amount;
+        let pool = &mut ctx.accounts.pool;
+        let user_opt = Some(&mut ctx.accounts.user);
+        update_rewards(pool, user_opt, None, total_staked, reward_vault_balance).unwrap();
+
+        // Transfer tokens from the pool vault back to the lock authority.
+        {
+            let decimals = token_interface::mint(&ctx.accounts.staking_mint)?.decimals;
+            let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
+            let pool_signer = &[&seeds[..]];
+
+            let cpi_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.staking_vault.to_account_info(),
+                    mint: ctx.accounts.staking_mint.to_account_info(),
+                    to: ctx.accounts.locked_token_account.to_account_info(),
+                    authority: ctx.accounts.pool_signer.to_account_info(),
+                },
+                pool_signer,
+            );
+            token_interface::transfer_checked(cpi_ctx, amount, decimals)?;
+        }
+
+        ctx.accounts.user.balance_staked = ctx
+            .accounts
+            .user
+            .balance_staked
+            .checked_sub(amount)
+            .unwrap();
+        ctx.accounts.user.balance_locked = ctx
+            .accounts
+            .user
+            .balance_locked
+            .checked_sub(amount)
+            .unwrap();
+        if ctx.accounts.user.balance_locked == 0 {
+            ctx.accounts.user.lock_authority = Pubkey::default();
+        }
+
+        Ok(())
+    }
+
     /// Fund the pool with rewards
     pub fn fund(ctx: Context<Fund>, amount: u64) -> Result<()> {
-        // Transfer reward A tokens into the A vault.
+        // Settle what accrued against the vault as it was, so the new funds only pay from now on
+        let total_staked = token_account(&ctx.accounts.staking_vault)?.amount;
+        let vault_before = token_account(&ctx.accounts.reward_vault)?.amount;
+        let pool = &mut ctx.accounts.pool;
+        update_rewards(pool, None, None, total_staked, vault_before).unwrap();
+
+        // Transfer reward A tokens into the A vault, crediting what arrives net of any transfer fee.
+        let mut received = 0;
         if amount > 0 {
+            let decimals = token_interface::mint(&ctx.accounts.reward_mint)?.decimals;
             let cpi_ctx = CpiContext::new(
                 ctx.accounts.token_program.to_account_info(),
-                token::Transfer {
+                TransferChecked {
                     from: ctx.accounts.from.to_account_info(),
+                    mint: ctx.accounts.reward_mint.to_account_info(),
                     to: ctx.accounts.reward_vault.to_account_info(),
                     authority: ctx.accounts.funder.to_account_info(),
                 },
             );
+            token_interface::transfer_checked(cpi_ctx, amount, decimals)?;
 
-            token::transfer(cpi_ctx, amount)?;
+            received = token_account(&ctx.accounts.reward_vault)?
+                .amount
+                .checked_sub(vault_before)
+                .unwrap();
         }
 
+        let pool = &mut ctx.accounts.pool;
+        if pool.calculator_kind == CalculatorKind::FundedLinear {
+            // Spread the new funds plus whatever is left of the current period over a fresh period
+            let now: u64 = clock::Clock::get().unwrap().unix_timestamp.try_into().unwrap();
+            let leftover = pool
+                .reward_duration_end
+                .saturating_sub(now)
+                .checked_mul(pool.reward_rate)
+                .unwrap();
+            pool.reward_rate = received
+                .checked_add(leftover)
+                .unwrap()
+                .checked_div(pool.reward_duration)
+                .unwrap();
+            pool.last_update_time = now;
+            pool.reward_duration_end = now.checked_add(pool.reward_duration).unwrap();
+        }
+
+        pool.reward_undistributed = pool.reward_undistributed.checked_add(received).unwrap();
+
         Ok(())
     }
 
     /// A user claiming rewards
     pub fn claim(ctx: Context<ClaimReward>, current_ts: i64) -> Result<()> {
-        let total_staked = ctx.accounts.staking_vault.amount;
+        let total_staked = token_account(&ctx.accounts.staking_vault)?.amount;
+        let reward_vault_balance = token_account(&ctx.accounts.reward_vault)?.amount;
 
         let pool = &mut ctx.accounts.pool;
         let user_opt = Some(&mut ctx.accounts.user);
-        update_rewards(pool, user_opt, None, total_staked).unwrap();
+        update_rewards(pool, user_opt, None, total_staked, reward_vault_balance).unwrap();
 
         let seeds = &[
             ctx.accounts.pool.to_account_info().key.as_ref(),
@@ -295,27 +668,53 @@ pub mod staking_contract {
         let pool_signer = &[&seeds[..]];
 
         if ctx.accounts.user.reward_per_token_pending > 0 {
-            let mut reward_amount = ctx.accounts.user.reward_per_token_pending;
-            let vault_balance = ctx.accounts.reward_vault.amount;
-
-            if vault_balance < reward_amount {
-                ctx.accounts.user.reward_per_token_pending = reward_amount - vault_balance;
-                reward_amount = vault_balance;
-            } else {
-                ctx.accounts.user.reward_per_token_pending = 0;
+            let requested = ctx.accounts.user.reward_per_token_pending;
+            let vault_balance = token_account(&ctx.accounts.reward_vault)?.amount;
+
+            let (reward_amount, unpaid) = settle_reward_claim(
+                &mut ctx.accounts.pool,
+                &mut ctx.accounts.user.reward_debt,
+                requested,
+                vault_balance,
+            );
+            ctx.accounts.user.reward_per_token_pending = unpaid;
+
+            if unpaid > 0 {
+                emit!(RewardClaimPartiallyFilled {
+                    pool: ctx.accounts.pool.key(),
+                    claimant: ctx.accounts.user.key(),
+                    requested,
+                    paid: reward_amount,
+                    unpaid,
+                    underfunded_by: ctx.accounts.pool.reward_owed_total.saturating_sub(vault_balance - reward_amount),
+                });
             }
 
             if reward_amount > 0 {
+                let decimals = token_interface::mint(&ctx.accounts.reward_mint)?.decimals;
                 let cpi_ctx = CpiContext::new_with_signer(
                     ctx.accounts.token_program.to_account_info(),
-                    token::Transfer {
+                    TransferChecked {
                         from: ctx.accounts.reward_vault.to_account_info(),
+                        mint: ctx.accounts.reward_mint.to_account_info(),
                         to: ctx.accounts.reward_account.to_account_info(),
                         authority: ctx.accounts.pool_signer.to_account_info(),
                     },
                     pool_signer,
                 );
-                token::transfer(cpi_ctx, reward_amount)?;
+                token_interface::transfer_checked(cpi_ctx, reward_amount, decimals)?;
+
+                let now = clock::Clock::get().unwrap().unix_timestamp.try_into().unwrap();
+                emit!(RewardClaimed {
+                    pool: ctx.accounts.pool.key(),
+                    owner: ctx.accounts.owner.key(),
+                    amount: reward_amount,
+                    effective_apr_bps: ctx
+                        .accounts
+                        .pool
+                        .rewards(vault_balance - reward_amount)
+                        .effective_apr_bps(total_staked, now),
+                });
 
                 ctx.accounts.user.claimed_ts = current_ts;
                 ctx.accounts.user.claimed_count = ctx
@@ -330,6 +729,74 @@ pub mod staking_contract {
         Ok(())
     }
 
+    /// Reports how far the reward vault falls short of the rewards owed to stakers, claimed
+    /// or not, including what accrued since the pool was last updated.
+    /// The figure is emitted and set as return data so it can be read by simulating the instruction.
+    pub fn reward_shortfall(ctx: Context<RewardShortfall>) -> Result<()> {
+        let now = current_timestamp()?;
+        let total_staked = token_account(&ctx.accounts.staking_vault)?.amount;
+        let reward_vault_balance = token_account(&ctx.accounts.reward_vault)?.amount;
+        let pool = &ctx.accounts.pool;
+        let mut rewards = pool.rewards(reward_vault_balance);
+        rewards.accrue(total_staked, now);
+
+        let underfunded_by = rewards.reward_owed_total.saturating_sub(reward_vault_balance);
+
+        emit!(RewardShortfallReported {
+            pool: pool.key(),
+            reward_owed_total: rewards.reward_owed_total,
+            reward_debt_total: pool.reward_debt_total,
+            reward_vault_balance,
+            underfunded_by,
+        });
+        solana_program::program::set_return_data(&underfunded_by.to_le_bytes());
+
+        Ok(())
+    }
+
+    /// Reports the reward a user has pending at the current time, without updating any account.
+    /// The figure is emitted and set as return data so it can be read by simulating the instruction.
+    pub fn pending_reward(ctx: Context<PendingReward>) -> Result<()> {
+        let now = current_timestamp()?;
+        let total_staked = token_account(&ctx.accounts.staking_vault)?.amount;
+        let reward_vault_balance = token_account(&ctx.accounts.reward_vault)?.amount;
+        let pending_reward = ctx
+            .accounts
+            .pool
+            .rewards(reward_vault_balance)
+            .user_earned_amount(&ctx.accounts.user.position(), total_staked, now);
+
+        emit!(PendingRewardReported {
+            pool: ctx.accounts.pool.key(),
+            user: ctx.accounts.user.key(),
+            pending_reward,
+        });
+        solana_program::program::set_return_data(&pending_reward.to_le_bytes());
+
+        Ok(())
+    }
+
+    /// Reports the pool's reward rate at the current time annualised, in basis points of the
+    /// total staked, without updating any account.
+    /// The figure is emitted and set as return data so it can be read by simulating the instruction.
+    pub fn effective_apr(ctx: Context<EffectiveApr>) -> Result<()> {
+        let now = current_timestamp()?;
+        let total_staked = token_account(&ctx.accounts.staking_vault)?.amount;
+        let reward_vault_balance = token_account(&ctx.accounts.reward_vault)?.amount;
+        let mut rewards = ctx.accounts.pool.rewards(reward_vault_balance);
+        rewards.accrue(total_staked, now);
+        let effective_apr_bps = rewards.effective_apr_bps(total_staked, now);
+
+        emit!(EffectiveAprReported {
+            pool: ctx.accounts.pool.key(),
+            total_staked,
+            effective_apr_bps,
+        });
+        solana_program::program::set_return_data(&effective_apr_bps.to_le_bytes());
+
+        Ok(())
+    }
+
     /// Closes a users stake account. Validation is done to ensure this is only allowed when
     /// the user has nothing staked and no rewards pending.
     pub fn close_user(ctx: Context<CloseUser>) -> Result<()> {
@@ -348,41 +815,30 @@ pub mod staking_contract {
         ];
 
         //close staking vault
-        let ix = spl_token::instruction::transfer(
-            &spl_token::ID,
-            ctx.accounts.staking_vault.to_account_info().key,
-            ctx.accounts.staking_refundee.to_account_info().key,
-            ctx.accounts.pool_signer.key,
-            &[ctx.accounts.pool_signer.key],
-            ctx.accounts.staking_vault.amount,
-        )?;
-        solana_program::program::invoke_signed(
-            &ix,
-            &[
+        let decimals = token_interface::mint(&ctx.accounts.staking_mint)?.decimals;
+        token_interface::transfer_checked(
+            CpiContext::new_with_signer(
                 ctx.accounts.token_program.to_account_info(),
-                ctx.accounts.staking_vault.to_account_info(),
-                ctx.accounts.staking_refundee.to_account_info(),
-                ctx.accounts.pool_signer.to_account_info(),
-            ],
-            &[signer_seeds],
-        )?;
-        let ix = spl_token::instruction::close_account(
-            &spl_token::ID,
-            ctx.accounts.staking_vault.to_account_info().key,
-            ctx.accounts.refundee.key,
-            ctx.accounts.pool_signer.key,
-            &[ctx.accounts.pool_signer.key],
+                TransferChecked {
+                    from: ctx.accounts.staking_vault.to_account_info(),
+                    mint: ctx.accounts.staking_mint.to_account_info(),
+                    to: ctx.accounts.staking_refundee.to_account_info(),
+                    authority: ctx.accounts.pool_signer.to_account_info(),
+                },
+                &[signer_seeds],
+            ),
+            token_account(&ctx.accounts.staking_vault)?.amount,
+            decimals,
         )?;
-        solana_program::program::invoke_signed(
-            &ix,
-            &[
-                ctx.accounts.token_program.to_account_info(),
-                ctx.accounts.staking_vault.to_account_info(),
-                ctx.accounts.refundee.to_account_info(),
-                ctx.accounts.pool_signer.to_account_info(),
-            ],
+        token_interface::close_account(CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            CloseAccount {
+                account: ctx.accounts.staking_vault.to_account_info(),
+                destination: ctx.accounts.refundee.to_account_info(),
+                authority: ctx.accounts.pool_signer.to_account_info(),
+            },
             &[signer_seeds],
-        )?;
+        ))?;
 
         Ok(())
     }
@@ -405,6 +861,7 @@ pub mod staking_contract {
 
         merchant.reward_per_token_complete = 0;
         merchant.reward_per_token_pending = 0;
+        merchant.reward_debt = 0;
         
         merchant.balance_staked = 0;
         merchant.merchant_user_stake_count = 0;
@@ -452,9 +909,10 @@ pub mod staking_contract {
 
         let pool = &mut ctx.accounts.pool;
 
-        let total_staked = ctx.accounts.staking_vault.amount;
+        let total_staked = token_account(&ctx.accounts.staking_vault)?.amount;
+        let reward_vault_balance = token_account(&ctx.accounts.reward_vault)?.amount;
         let merchant_opt = Some(&mut ctx.accounts.merchant);
-        update_rewards(pool, None, merchant_opt, total_staked).unwrap();
+        update_rewards(pool, None, merchant_opt, total_staked, reward_vault_balance).unwrap();
 
         if pool.paused {
             return Err(ErrorCode::PoolPaused.into());
@@ -493,18 +951,38 @@ pub mod staking_contract {
             ctx.accounts.merchant_user.claimed_ts = current_ts;
         }
 
+        // Transfer tokens into the stake vault.
+        {
+            let decimals = token_interface::mint(&ctx.accounts.staking_mint)?.decimals;
+            let cpi_ctx = CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.stake_from_account.to_account_info(),
+                    mint: ctx.accounts.staking_mint.to_account_info(),
+                    to: ctx.accounts.staking_vault.to_account_info(),
+                    authority: ctx.accounts.owner.to_account_info(), //todo use user account as signer
+                },
+            );
+            token_interface::transfer_checked(cpi_ctx, amount, decimals)?;
+        }
+        // Credit what reached the vault, net of any transfer fee.
+        let received = token_account(&ctx.accounts.staking_vault)?
+            .amount
+            .checked_sub(total_staked)
+            .unwrap();
+
         ctx.accounts.merchant.balance_staked = ctx
             .accounts
             .merchant
             .balance_staked
-            .checked_add(amount)
+            .checked_add(received)
             .unwrap();
 
         ctx.accounts.merchant_user.balance_staked = ctx
             .accounts
             .merchant_user
             .balance_staked
-            .checked_add(amount)
+            .checked_add(received)
             .unwrap();
         ctx.accounts.merchant_user.staked_ts = current_ts;
         ctx.accounts.merchant_user.staked_count = ctx
@@ -514,19 +992,6 @@ pub mod staking_contract {
             .checked_add(1)
             .unwrap();
 
-        // Transfer tokens into the stake vault.
-        {
-            let cpi_ctx = CpiContext::new(
-                ctx.accounts.token_program.to_account_info(),
-                token::Transfer {
-                    from: ctx.accounts.stake_from_account.to_account_info(),
-                    to: ctx.accounts.staking_vault.to_account_info(),
-                    authority: ctx.accounts.owner.to_account_info(), //todo use user account as signer
-                },
-            );
-            token::transfer(cpi_ctx, amount)?;
-        }
-
         Ok(())
     }
 
@@ -550,25 +1015,28 @@ pub mod staking_contract {
             return Err(ErrorCode::InsufficientFundUnstake.into());
         }
 
-        let total_staked = ctx.accounts.staking_vault.amount;
+        let total_staked = token_account(&ctx.accounts.staking_vault)?.amount;
+        let reward_vault_balance = token_account(&ctx.accounts.reward_vault)?.amount;
         let merchant_opt = Some(&mut ctx.accounts.merchant);
-        update_rewards(pool, None, merchant_opt, total_staked).unwrap();
+        update_rewards(pool, None, merchant_opt, total_staked, reward_vault_balance).unwrap();
 
         // Transfer tokens from the pool vault to user vault.
         {
+            let decimals = token_interface::mint(&ctx.accounts.staking_mint)?.decimals;
             let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
             let pool_signer = &[&seeds[..]];
 
             let cpi_ctx = CpiContext::new_with_signer(
                 ctx.accounts.token_program.to_account_info(),
-                token::Transfer {
+                TransferChecked {
                     from: ctx.accounts.staking_vault.to_account_info(),
+                    mint: ctx.accounts.staking_mint.to_account_info(),
                     to: ctx.accounts.stake_from_account.to_account_info(),
                     authority: ctx.accounts.pool_signer.to_account_info(),
                 },
                 pool_signer,
             );
-            token::transfer(cpi_ctx, amount)?;
+            token_interface::transfer_checked(cpi_ctx, amount, decimals)?;
         }
 
         ctx.accounts.merchant_user.balance_staked = ctx
@@ -607,7 +1075,8 @@ pub mod staking_contract {
 
     /// claim merchant reward for whole pool
     pub fn claim_reward_for_merchant(ctx: Context<ClaimRewardForMerchant>, current_ts: i64) -> Result<()> {
-        let total_staked = ctx.accounts.staking_vault.amount;
+        let total_staked = token_account(&ctx.accounts.staking_vault)?.amount;
+        let reward_vault_balance = token_account(&ctx.accounts.reward_vault)?.amount;
 
         let pool = &mut ctx.accounts.pool;
 
@@ -616,7 +1085,7 @@ pub mod staking_contract {
         }
         
         let merchant_opt = Some(&mut ctx.accounts.merchant);
-        update_rewards(pool, None, merchant_opt, total_staked).unwrap();
+        update_rewards(pool, None, merchant_opt, total_staked, reward_vault_balance).unwrap();
 
         let seeds = &[
             ctx.accounts.pool.to_account_info().key.as_ref(),
@@ -625,28 +1094,41 @@ pub mod staking_contract {
         let pool_signer = &[&seeds[..]];
 
         if ctx.accounts.merchant.reward_per_token_pending > 0 {
-            let mut reward_amount = ctx.accounts.merchant.reward_per_token_pending;
-            let vault_balance = ctx.accounts.reward_vault.amount;
-
-            if vault_balance < reward_amount {
-                reward_amount = vault_balance;
-
-                ctx.accounts.merchant.reward_per_token_pending = reward_amount - vault_balance;
-            } else {
-                ctx.accounts.merchant.reward_per_token_pending = 0;
+            let requested = ctx.accounts.merchant.reward_per_token_pending;
+            let vault_balance = token_account(&ctx.accounts.reward_vault)?.amount;
+
+            let (reward_amount, unpaid) = settle_reward_claim(
+                &mut ctx.accounts.pool,
+                &mut ctx.accounts.merchant.reward_debt,
+                requested,
+                vault_balance,
+            );
+            ctx.accounts.merchant.reward_per_token_pending = unpaid;
+
+            if unpaid > 0 {
+                emit!(RewardClaimPartiallyFilled {
+                    pool: ctx.accounts.pool.key(),
+                    claimant: ctx.accounts.merchant.key(),
+                    requested,
+                    paid: reward_amount,
+                    unpaid,
+                    underfunded_by: ctx.accounts.pool.reward_owed_total.saturating_sub(vault_balance - reward_amount),
+                });
             }
 
             if reward_amount > 0 {
+                let decimals = token_interface::mint(&ctx.accounts.reward_mint)?.decimals;
                 let cpi_ctx = CpiContext::new_with_signer(
                     ctx.accounts.token_program.to_account_info(),
-                    token::Transfer {
+                    TransferChecked {
                         from: ctx.accounts.reward_vault.to_account_info(),
+                        mint: ctx.accounts.reward_mint.to_account_info(),
                         to: ctx.accounts.reward_account.to_account_info(),
                         authority: ctx.accounts.pool_signer.to_account_info(),
                     },
                     pool_signer,
                 );
-                token::transfer(cpi_ctx, reward_amount)?;
+                token_interface::transfer_checked(cpi_ctx, reward_amount, decimals)?;
 
                 ctx.accounts.merchant.last_updated_ts = current_ts;
             }
@@ -675,9 +1157,10 @@ pub mod staking_contract {
             return Err(ErrorCode::PoolPaused.into());
         }
 
-        let total_staked = ctx.accounts.staking_vault.amount;
+        let total_staked = token_account(&ctx.accounts.staking_vault)?.amount;
+        let reward_vault_balance = token_account(&ctx.accounts.reward_vault)?.amount;
         let user_opt = Some(&mut ctx.accounts.user);
-        update_rewards(pool, user_opt, None, total_staked).unwrap();
+        update_rewards(pool, user_opt, None, total_staked, reward_vault_balance).unwrap();
 
         if ctx.accounts.user.staked_count == 0 {
             ctx.accounts.user.claimed_ts = current_ts;
@@ -688,14 +1171,34 @@ pub mod staking_contract {
             ctx.accounts.user.claimed_ts = current_ts;
         }
 
+        // Transfer tokens into the stake vault.
+        {
+            let decimals = token_interface::mint(&ctx.accounts.staking_mint)?.decimals;
+            let cpi_ctx = CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.stake_from_account.to_account_info(),
+                    mint: ctx.accounts.staking_mint.to_account_info(),
+                    to: ctx.accounts.staking_vault.to_account_info(),
+                    authority: ctx.accounts.owner.to_account_info(), //todo use user account as signer
+                },
+            );
+            token_interface::transfer_checked(cpi_ctx, amount, decimals)?;
+        }
+        // Credit what reached the vault, net of any transfer fee.
+        let received = token_account(&ctx.accounts.staking_vault)?
+            .amount
+            .checked_sub(total_staked)
+            .unwrap();
+
         let user = &mut ctx.accounts.user;
         user.behalf_staked_ts.push(current_ts);
-        user.behalf_staked_amount.push(amount);
+        user.behalf_staked_amount.push(received);
         user.behalf_claimed_status.push(false);
 
         user.balance_staked = user
             .balance_staked
-            .checked_add(amount)
+            .checked_add(received)
             .unwrap();
         user.staked_ts = current_ts;
         user.staked_count = user
@@ -706,19 +1209,6 @@ pub mod staking_contract {
         if !pool.passive_stakers_list.contains(&ctx.accounts.user.to_account_info().key) {
             pool.passive_stakers_list.push(*ctx.accounts.user.to_account_info().key);
         }
-            
-        // Transfer tokens into the stake vault.
-        {
-            let cpi_ctx = CpiContext::new(
-                ctx.accounts.token_program.to_account_info(),
-                token::Transfer {
-                    from: ctx.accounts.stake_from_account.to_account_info(),
-                    to: ctx.accounts.staking_vault.to_account_info(),
-                    authority: ctx.accounts.owner.to_account_info(), //todo use user account as signer
-                },
-            );
-            token::transfer(cpi_ctx, amount)?;
-        }
 
         Ok(())
     }
@@ -745,25 +1235,28 @@ pub mod staking_contract {
             return Err(ErrorCode::PoolPaused.into());
         }
 
-        let total_staked = ctx.accounts.staking_vault.amount;
+        let total_staked = token_account(&ctx.accounts.staking_vault)?.amount;
+        let reward_vault_balance = token_account(&ctx.accounts.reward_vault)?.amount;
         let user_opt = Some(&mut ctx.accounts.user);
-        update_rewards(pool, user_opt, None, total_staked).unwrap();
+        update_rewards(pool, user_opt, None, total_staked, reward_vault_balance).unwrap();
 
         // Transfer tokens from the pool vault to user vault.
         {
+            let decimals = token_interface::mint(&ctx.accounts.staking_mint)?.decimals;
             let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
             let pool_signer = &[&seeds[..]];
 
             let cpi_ctx = CpiContext::new_with_signer(
                 ctx.accounts.token_program.to_account_info(),
-                token::Transfer {
+                TransferChecked {
                     from: ctx.accounts.staking_vault.to_account_info(),
+                    mint: ctx.accounts.staking_mint.to_account_info(),
                     to: ctx.accounts.stake_from_account.to_account_info(),
                     authority: ctx.accounts.pool_signer.to_account_info(),
                 },
                 pool_signer,
             );
-            token::transfer(cpi_ctx, amount_to_transfer)?;
+            token_interface::transfer_checked(cpi_ctx, amount_to_transfer, decimals)?;
         }
 
         let user = &mut ctx.accounts.user;
@@ -775,6 +1268,42 @@ pub mod staking_contract {
 
         Ok(())
     }
+
+    //////////////////////////////////////
+    // Governance
+    //////////////////////////////////////
+
+    /// Creates the voter weight record a user presents to spl-governance
+    pub fn create_voter_weight_record(
+        ctx: Context<CreateVoterWeightRecord>,
+        realm: Pubkey,
+        governing_token_mint: Pubkey,
+    ) -> Result<()> {
+        let record = &mut ctx.accounts.voter_weight_record;
+        record.realm = realm;
+        record.governing_token_mint = governing_token_mint;
+        record.governing_token_owner = ctx.accounts.owner.key();
+        record.voter_weight = 0;
+        record.voter_weight_expiry = Some(0);
+        record.weight_action = None;
+        record.weight_action_target = None;
+
+        Ok(())
+    }
+
+    /// Refreshes a voter weight record from the user's staked positions.
+    /// The weight expires at the current slot, so this has to be bundled with the governance instruction.
+    pub fn update_voter_weight_record(ctx: Context<UpdateVoterWeightRecord>) -> Result<()> {
+        let clock = clock::Clock::get().unwrap();
+
+        let record = &mut ctx.accounts.voter_weight_record;
+        record.voter_weight = voting_power(&ctx.accounts.user, clock.unix_timestamp);
+        record.voter_weight_expiry = Some(clock.slot);
+        record.weight_action = None;
+        record.weight_action_target = None;
+
+        Ok(())
+    }
 }
 
 #[derive(Accounts)]
@@ -783,30 +1312,38 @@ pub struct InitializeMainPool<'info> {
     /// CHECK: This is not dangerous because we don't read or write from this account
     authority: Signer<'info>,
 
-    staking_mint: Box<Account<'info, Mint>>,
+    #[account(
+        constraint = token_interface::is_supported_mint(&staking_mint)? @ ErrorCode::UnsupportedMintExtension,
+    )]
+    /// CHECK: The mint of the staked token, read through `token_interface`
+    staking_mint: UncheckedAccount<'info>,
 
     #[account(
-        init,
+        mut,
         seeds = [STAKING_VAULT_PDA_SEED, &pool_token_ata.to_account_info().key.to_bytes()], bump,
-        payer = authority,
-        token::mint = staking_mint,
-        token::authority = pool_signer,
     )]
-    staking_vault: Box<Account<'info, TokenAccount>>,
+    /// CHECK: Created by the instruction, sized for the extensions of the staking mint
+    staking_vault: UncheckedAccount<'info>,
 
-    reward_mint: Box<Account<'info, Mint>>,
+    #[account(
+        constraint = token_interface::is_supported_mint(&reward_mint)? @ ErrorCode::UnsupportedMintExtension,
+    )]
+    /// CHECK: The mint of the reward token, read through `token_interface`
+    reward_mint: UncheckedAccount<'info>,
 
     #[account(
-        init,
+        mut,
         seeds = [REWARD_VAULT_PDA_SEED, &pool_token_ata.to_account_info().key.to_bytes()], bump,
-        payer = authority,
-        token::mint = reward_mint,
-        token::authority = pool_signer,
     )]
-    reward_vault: Box<Account<'info, TokenAccount>>,
+    /// CHECK: Created by the instruction, sized for the extensions of the reward mint
+    reward_vault: UncheckedAccount<'info>,
 
-    #[account(mut)]
-    pub pool_token_ata: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = token_account(&pool_token_ata).is_ok(),
+    )]
+    /// CHECK: A token account of either token program, only used to derive the vault addresses
+    pub pool_token_ata: UncheckedAccount<'info>,
 
     #[account(
         seeds = [
@@ -824,11 +1361,54 @@ pub struct InitializeMainPool<'info> {
     )]
     pool: Box<Account<'info, Pool>>,
 
-    token_program: Program<'info, Token>,
+    #[account(constraint = *staking_mint.owner == staking_token_program.key() @ ErrorCode::InvalidTokenProgram)]
+    /// CHECK: The token program of the staking mint
+    staking_token_program: UncheckedAccount<'info>,
+    #[account(constraint = *reward_mint.owner == reward_token_program.key() @ ErrorCode::InvalidTokenProgram)]
+    /// CHECK: The token program of the reward mint
+    reward_token_program: UncheckedAccount<'info>,
     rent: Sysvar<'info, Rent>,
     system_program: Program<'info, System>,
 }
 
+impl<'info> InitializeMainPool<'info> {
+    /// Creates a vault of `mint` at a PDA, sized for the mint's extensions and held by the
+    /// pool signer
+    fn create_vault(
+        &self,
+        vault: &AccountInfo<'info>,
+        mint: &AccountInfo<'info>,
+        token_program: &AccountInfo<'info>,
+        seeds: &[&[u8]],
+    ) -> ProgramResult {
+        let space = token_interface::account_space(mint, token_program)?;
+        solana_program::program::invoke_signed(
+            &solana_program::system_instruction::create_account(
+                self.authority.key,
+                vault.key,
+                self.rent.minimum_balance(space),
+                space as u64,
+                token_program.key,
+            ),
+            &[
+                self.authority.to_account_info(),
+                vault.clone(),
+                self.system_program.to_account_info(),
+            ],
+            &[seeds],
+        )?;
+        token_interface::initialize_account(CpiContext::new(
+            token_program.clone(),
+            InitializeAccount {
+                account: vault.clone(),
+                mint: mint.clone(),
+                authority: self.pool_signer.to_account_info(),
+                rent: self.rent.to_account_info(),
+            },
+        ))
+    }
+}
+
 #[derive(Accounts)]
 #[instruction(nonce: u8)]
 pub struct CreateUser<'info> {
@@ -900,19 +1480,68 @@ pub struct Unpause<'info> {
     token_program: Program<'info, Token>,
 }
 
+#[derive(Accounts)]
+pub struct SetTargetApr<'info> {
+    #[account(
+        mut,
+        has_one = authority,
+        has_one = staking_vault,
+        has_one = reward_vault,
+    )]
+    pool: Box<Account<'info, Pool>>,
+    authority: Signer<'info>,
+    /// CHECK: The pool's staking vault, read through `token_interface`
+    staking_vault: UncheckedAccount<'info>,
+    /// CHECK: The pool's reward vault, read through `token_interface`
+    reward_vault: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetRealm<'info> {
+    #[account(
+        mut,
+        has_one = authority,
+    )]
+    pool: Box<Account<'info, Pool>>,
+    authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AddEmissionPhase<'info> {
+    #[account(
+        mut,
+        has_one = authority,
+        has_one = staking_vault,
+        has_one = reward_vault,
+    )]
+    pool: Box<Account<'info, Pool>>,
+    authority: Signer<'info>,
+    /// CHECK: The pool's staking vault, read through `token_interface`
+    staking_vault: UncheckedAccount<'info>,
+    /// CHECK: The pool's reward vault, read through `token_interface`
+    reward_vault: UncheckedAccount<'info>,
+}
+
 #[derive(Accounts)]
 pub struct Stake<'info> {
     // Global accounts for the staking instance.
     #[account(
         mut,
         has_one = staking_vault,
+        has_one = reward_vault,
     )]
     pool: Box<Account<'info, Pool>>,
     #[account(
         mut,
-        constraint = staking_vault.owner == *pool_signer.key,
+        constraint = token_account(&staking_vault)?.owner == *pool_signer.key,
     )]
-    staking_vault: Box<Account<'info, TokenAccount>>,
+    /// CHECK: The pool's staking vault, read through `token_interface`
+    staking_vault: UncheckedAccount<'info>,
+    /// CHECK: The pool's reward vault, read through `token_interface`
+    reward_vault: UncheckedAccount<'info>,
+    #[account(address = pool.staking_mint)]
+    /// CHECK: The pool's staking mint
+    staking_mint: UncheckedAccount<'info>,
 
     // User.
     #[account(
@@ -928,7 +1557,8 @@ pub struct Stake<'info> {
     user: Box<Account<'info, User>>,
     owner: Signer<'info>,
     #[account(mut)]
-    stake_from_account: Box<Account<'info, TokenAccount>>,
+    /// CHECK: The owner's token account, checked by the token program
+    stake_from_account: UncheckedAccount<'info>,
 
     // Program signers.
     #[account(
@@ -941,7 +1571,70 @@ pub struct Stake<'info> {
     pool_signer: UncheckedAccount<'info>,
 
     // Misc.
-    token_program: Program<'info, Token>,
+    #[account(address = pool.staking_token_program)]
+    /// CHECK: The token program of the staking mint
+    token_program: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct StakeLocked<'info> {
+    // Global accounts for the staking instance.
+    #[account(
+        mut,
+        has_one = staking_vault,
+        has_one = reward_vault,
+    )]
+    pool: Box<Account<'info, Pool>>,
+    #[account(
+        mut,
+        constraint = token_account(&staking_vault)?.owner == *pool_signer.key,
+    )]
+    /// CHECK: The pool's staking vault, read through `token_interface`
+    staking_vault: UncheckedAccount<'info>,
+    /// CHECK: The pool's reward vault, read through `token_interface`
+    reward_vault: UncheckedAccount<'info>,
+    #[account(address = pool.staking_mint)]
+    /// CHECK: The pool's staking mint
+    staking_mint: UncheckedAccount<'info>,
+
+    // User.
+    #[account(
+        mut,
+        has_one = owner,
+        has_one = pool,
+        seeds = [
+            owner.key.as_ref(),
+            pool.to_account_info().key.as_ref()
+        ],
+        bump = user.nonce,
+    )]
+    user: Box<Account<'info, User>>,
+    /// CHECK: The user the locked position earns rewards for, checked through `user`.
+    /// Signs when staking, not when the lock authority unstakes.
+    owner: UncheckedAccount<'info>,
+    // Owns the locked tokens while they are not staked.
+    lock_authority: Signer<'info>,
+    #[account(
+        mut,
+        constraint = token_account(&locked_token_account)?.owner == lock_authority.key(),
+    )]
+    /// CHECK: A token account of the lock authority, read through `token_interface`
+    locked_token_account: UncheckedAccount<'info>,
+
+    // Program signers.
+    #[account(
+        seeds = [
+            pool.to_account_info().key.as_ref()
+        ],
+        bump = pool.nonce,
+    )]
+    /// CHECK: This is not dangerous because we don't read or write from this account
+    pool_signer: UncheckedAccount<'info>,
+
+    // Misc.
+    #[account(address = pool.staking_token_program)]
+    /// CHECK: The token program of the staking mint
+    token_program: UncheckedAccount<'info>,
 }
 
 #[derive(Accounts)]
@@ -966,16 +1659,22 @@ pub struct Fund<'info> {
     )]
     pool: Box<Account<'info, Pool>>,
     #[account(mut)]
-    staking_vault: Box<Account<'info, TokenAccount>>,
+    /// CHECK: The pool's staking vault, read through `token_interface`
+    staking_vault: UncheckedAccount<'info>,
     #[account(mut)]
-    reward_vault: Box<Account<'info, TokenAccount>>,
+    /// CHECK: The pool's reward vault, read through `token_interface`
+    reward_vault: UncheckedAccount<'info>,
+    #[account(address = pool.reward_mint)]
+    /// CHECK: The pool's reward mint
+    reward_mint: UncheckedAccount<'info>,
     #[account(
         //require signed funder auth - otherwise constant micro fund could hold funds hostage
         // constraint = funder.key() == pool.authority || pool.funders.iter().any(|x| *x == funder.key()),
     )]
     funder: Signer<'info>,
     #[account(mut)]
-    from: Box<Account<'info, TokenAccount>>,
+    /// CHECK: The funder's token account, checked by the token program
+    from: UncheckedAccount<'info>,
     // Program signers.
     #[account(
         seeds = [
@@ -987,7 +1686,9 @@ pub struct Fund<'info> {
     pool_signer: UncheckedAccount<'info>,
 
     // Misc.
-    token_program: Program<'info, Token>,
+    #[account(address = pool.reward_token_program)]
+    /// CHECK: The token program of the reward mint
+    token_program: UncheckedAccount<'info>,
 }
 
 #[derive(Accounts)]
@@ -1000,9 +1701,14 @@ pub struct ClaimReward<'info> {
     )]
     pool: Box<Account<'info, Pool>>,
     #[account(mut)]
-    staking_vault: Box<Account<'info, TokenAccount>>,
+    /// CHECK: The pool's staking vault, read through `token_interface`
+    staking_vault: UncheckedAccount<'info>,
     #[account(mut)]
-    reward_vault: Box<Account<'info, TokenAccount>>,
+    /// CHECK: The pool's reward vault, read through `token_interface`
+    reward_vault: UncheckedAccount<'info>,
+    #[account(address = pool.reward_mint)]
+    /// CHECK: The pool's reward mint
+    reward_mint: UncheckedAccount<'info>,
 
     // User.
     #[account(
@@ -1018,7 +1724,8 @@ pub struct ClaimReward<'info> {
     user: Box<Account<'info, User>>,
     owner: Signer<'info>,
     #[account(mut)]
-    reward_account: Box<Account<'info, TokenAccount>>,
+    /// CHECK: The claimant's token account, checked by the token program
+    reward_account: UncheckedAccount<'info>,
 
     // Program signers.
     #[account(
@@ -1031,7 +1738,52 @@ pub struct ClaimReward<'info> {
     pool_signer: UncheckedAccount<'info>,
 
     // Misc.
-    token_program: Program<'info, Token>,
+    #[account(address = pool.reward_token_program)]
+    /// CHECK: The token program of the reward mint
+    token_program: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RewardShortfall<'info> {
+    #[account(
+        has_one = staking_vault,
+        has_one = reward_vault,
+    )]
+    pool: Box<Account<'info, Pool>>,
+    /// CHECK: The pool's staking vault, read through `token_interface`
+    staking_vault: UncheckedAccount<'info>,
+    /// CHECK: The pool's reward vault, read through `token_interface`
+    reward_vault: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct PendingReward<'info> {
+    #[account(
+        has_one = staking_vault,
+        has_one = reward_vault,
+    )]
+    pool: Box<Account<'info, Pool>>,
+    /// CHECK: The pool's staking vault, read through `token_interface`
+    staking_vault: UncheckedAccount<'info>,
+    /// CHECK: The pool's reward vault, read through `token_interface`
+    reward_vault: UncheckedAccount<'info>,
+    #[account(
+        has_one = pool,
+    )]
+    user: Box<Account<'info, User>>,
+}
+
+#[derive(Accounts)]
+pub struct EffectiveApr<'info> {
+    #[account(
+        has_one = staking_vault,
+        has_one = reward_vault,
+    )]
+    pool: Box<Account<'info, Pool>>,
+    /// CHECK: The pool's staking vault, read through `token_interface`
+    staking_vault: UncheckedAccount<'info>,
+    /// CHECK: The pool's reward vault, read through `token_interface`
+    reward_vault: UncheckedAccount<'info>,
 }
 
 #[derive(Accounts)]
@@ -1049,6 +1801,8 @@ pub struct CloseUser<'info> {
         ],
         bump = user.nonce,
         constraint = user.balance_staked == 0,
+        constraint = user.reward_per_token_pending == 0 @ ErrorCode::RewardsPending,
+        constraint = user.reward_debt == 0 @ ErrorCode::RewardsPending,
     )]
     user: Account<'info, User>,
     owner: Signer<'info>,
@@ -1060,7 +1814,8 @@ pub struct ClosePool<'info> {
     /// CHECK: This is not dangerous because we don't read or write from this account
     refundee: UncheckedAccount<'info>,
     #[account(mut)]
-    staking_refundee: Box<Account<'info, TokenAccount>>,
+    /// CHECK: Receives what is left in the staking vault, checked by the token program
+    staking_refundee: UncheckedAccount<'info>,
 
     #[account(
         mut,
@@ -1075,11 +1830,16 @@ pub struct ClosePool<'info> {
     pool: Account<'info, Pool>,
     authority: Signer<'info>,
     #[account(mut,
-        constraint = staking_vault.amount == 0,
+        constraint = token_account(&staking_vault)?.amount == 0,
     )]
-    staking_vault: Box<Account<'info, TokenAccount>>,
+    /// CHECK: The pool's staking vault, read through `token_interface`
+    staking_vault: UncheckedAccount<'info>,
     #[account(mut)]
-    reward_vault: Box<Account<'info, TokenAccount>>,
+    /// CHECK: The pool's reward vault, read through `token_interface`
+    reward_vault: UncheckedAccount<'info>,
+    #[account(address = pool.staking_mint)]
+    /// CHECK: The pool's staking mint
+    staking_mint: UncheckedAccount<'info>,
     #[account(
         seeds = [
             pool.to_account_info().key.as_ref()
@@ -1088,7 +1848,9 @@ pub struct ClosePool<'info> {
     )]
     /// CHECK: This is not dangerous because we don't read or write from this account
     pool_signer: UncheckedAccount<'info>,
-    token_program: Program<'info, Token>,
+    #[account(address = pool.staking_token_program)]
+    /// CHECK: The token program of the staking mint
+    token_program: UncheckedAccount<'info>,
 }
 
 /////////////////////////////////////////////////////////
@@ -1161,13 +1923,20 @@ pub struct StakeTokenToMerchant<'info> {
         mut,
         constraint = !pool.paused,
         has_one = staking_vault,
+        has_one = reward_vault,
     )]
     pool: Box<Account<'info, Pool>>,
     #[account(
         mut,
-        constraint = staking_vault.owner == *pool_signer.key,
+        constraint = token_account(&staking_vault)?.owner == *pool_signer.key,
     )]
-    staking_vault: Box<Account<'info, TokenAccount>>,
+    /// CHECK: The pool's staking vault, read through `token_interface`
+    staking_vault: UncheckedAccount<'info>,
+    /// CHECK: The pool's reward vault, read through `token_interface`
+    reward_vault: UncheckedAccount<'info>,
+    #[account(address = pool.staking_mint)]
+    /// CHECK: The pool's staking mint
+    staking_mint: UncheckedAccount<'info>,
 
     // merchant
     #[account(
@@ -1192,7 +1961,8 @@ pub struct StakeTokenToMerchant<'info> {
     merchant_user: Box<Account<'info, MerchantUser>>,
     owner: Signer<'info>,
     #[account(mut)]
-    stake_from_account: Box<Account<'info, TokenAccount>>,
+    /// CHECK: The owner's token account, checked by the token program
+    stake_from_account: UncheckedAccount<'info>,
 
     // Program signers.
     #[account(
@@ -1205,7 +1975,9 @@ pub struct StakeTokenToMerchant<'info> {
     pool_signer: UncheckedAccount<'info>,
 
     // Misc.
-    token_program: Program<'info, Token>,
+    #[account(address = pool.staking_token_program)]
+    /// CHECK: The token program of the staking mint
+    token_program: UncheckedAccount<'info>,
 }
 
 #[derive(Accounts)]
@@ -1278,13 +2050,19 @@ pub struct ClaimRewardForMerchant<'info> {
     )]
     merchant: Box<Account<'info, Merchant>>,
     #[account(mut)]
-    staking_vault: Box<Account<'info, TokenAccount>>,
+    /// CHECK: The pool's staking vault, read through `token_interface`
+    staking_vault: UncheckedAccount<'info>,
     #[account(mut)]
-    reward_vault: Box<Account<'info, TokenAccount>>,
+    /// CHECK: The pool's reward vault, read through `token_interface`
+    reward_vault: UncheckedAccount<'info>,
+    #[account(address = pool.reward_mint)]
+    /// CHECK: The pool's reward mint
+    reward_mint: UncheckedAccount<'info>,
 
     owner: Signer<'info>,
     #[account(mut)]
-    reward_account: Box<Account<'info, TokenAccount>>,
+    /// CHECK: The claimant's token account, checked by the token program
+    reward_account: UncheckedAccount<'info>,
 
     // Program signers.
     #[account(
@@ -1297,7 +2075,9 @@ pub struct ClaimRewardForMerchant<'info> {
     pool_signer: UncheckedAccount<'info>,
 
     // Misc.
-    token_program: Program<'info, Token>,
+    #[account(address = pool.reward_token_program)]
+    /// CHECK: The token program of the reward mint
+    token_program: UncheckedAccount<'info>,
 }
 
 #[derive(Accounts)]
@@ -1315,13 +2095,18 @@ pub struct MerchantStake<'info> {
     merchant: Box<Account<'info, Merchant>>,
     #[account(
         mut,
-        constraint = staking_vault.owner == *pool_signer.key,
+        constraint = token_account(&staking_vault)?.owner == *pool_signer.key,
     )]
-    staking_vault: Box<Account<'info, TokenAccount>>,
+    /// CHECK: The pool's staking vault, read through `token_interface`
+    staking_vault: UncheckedAccount<'info>,
+    #[account(address = pool.staking_mint)]
+    /// CHECK: The pool's staking mint
+    staking_mint: UncheckedAccount<'info>,
 
     owner: Signer<'info>,
     #[account(mut)]
-    stake_from_account: Box<Account<'info, TokenAccount>>,
+    /// CHECK: The owner's token account, checked by the token program
+    stake_from_account: UncheckedAccount<'info>,
 
     // Program signers.
     #[account(
@@ -1334,7 +2119,9 @@ pub struct MerchantStake<'info> {
     pool_signer: UncheckedAccount<'info>,
 
     // Misc.
-    token_program: Program<'info, Token>,
+    #[account(address = pool.staking_token_program)]
+    /// CHECK: The token program of the staking mint
+    token_program: UncheckedAccount<'info>,
 }
 
 ////////////////////////////////////////////////////
@@ -1346,14 +2133,21 @@ pub struct StakeOnBehalf<'info> {
     #[account(
         mut,
         has_one = staking_vault,
+        has_one = reward_vault,
     )]
     pool: Box<Account<'info, Pool>>,
 
     #[account(
         mut,
-        constraint = staking_vault.owner == *pool_signer.key,
+        constraint = token_account(&staking_vault)?.owner == *pool_signer.key,
     )]
-    staking_vault: Box<Account<'info, TokenAccount>>,
+    /// CHECK: The pool's staking vault, read through `token_interface`
+    staking_vault: UncheckedAccount<'info>,
+    /// CHECK: The pool's reward vault, read through `token_interface`
+    reward_vault: UncheckedAccount<'info>,
+    #[account(address = pool.staking_mint)]
+    /// CHECK: The pool's staking mint
+    staking_mint: UncheckedAccount<'info>,
 
     #[account(
         mut,
@@ -1363,7 +2157,8 @@ pub struct StakeOnBehalf<'info> {
 
     owner: Signer<'info>,
     #[account(mut)]
-    stake_from_account: Box<Account<'info, TokenAccount>>,
+    /// CHECK: The owner's token account, checked by the token program
+    stake_from_account: UncheckedAccount<'info>,
 
     // Program signers.
     #[account(
@@ -1375,7 +2170,9 @@ pub struct StakeOnBehalf<'info> {
     /// CHECK: This is not dangerous because we don't read or write from this account
     pool_signer: UncheckedAccount<'info>,
     // Misc
-    token_program: Program<'info, Token>,
+    #[account(address = pool.staking_token_program)]
+    /// CHECK: The token program of the staking mint
+    token_program: UncheckedAccount<'info>,
 }
 
 #[derive(Accounts)]
@@ -1384,13 +2181,20 @@ pub struct Withdraw<'info> {
     #[account(
         mut,
         has_one = staking_vault,
+        has_one = reward_vault,
     )]
     pool: Box<Account<'info, Pool>>,
     #[account(
         mut,
-        constraint = staking_vault.owner == *pool_signer.key,
+        constraint = token_account(&staking_vault)?.owner == *pool_signer.key,
     )]
-    staking_vault: Box<Account<'info, TokenAccount>>,
+    /// CHECK: The pool's staking vault, read through `token_interface`
+    staking_vault: UncheckedAccount<'info>,
+    /// CHECK: The pool's reward vault, read through `token_interface`
+    reward_vault: UncheckedAccount<'info>,
+    #[account(address = pool.staking_mint)]
+    /// CHECK: The pool's staking mint
+    staking_mint: UncheckedAccount<'info>,
 
     // User.
     #[account(
@@ -1406,7 +2210,8 @@ pub struct Withdraw<'info> {
     user: Box<Account<'info, User>>,
     owner: Signer<'info>,
     #[account(mut)]
-    stake_from_account: Box<Account<'info, TokenAccount>>,
+    /// CHECK: The owner's token account, checked by the token program
+    stake_from_account: UncheckedAccount<'info>,
 
     // Program signers.
     #[account(
@@ -1419,7 +2224,66 @@ pub struct Withdraw<'info> {
     pool_signer: UncheckedAccount<'info>,
 
     // Misc.
-    token_program: Program<'info, Token>,
+    #[account(address = pool.staking_token_program)]
+    /// CHECK: The token program of the staking mint
+    token_program: UncheckedAccount<'info>,
+}
+
+////////////////////////////////////////////////////
+/// Governance
+////////////////////////////////////////////////////
+#[derive(Accounts)]
+#[instruction(realm: Pubkey, governing_token_mint: Pubkey)]
+pub struct CreateVoterWeightRecord<'info> {
+    #[account(
+        constraint = pool.realm != Pubkey::default() && pool.realm == realm @ ErrorCode::InvalidRealm,
+        constraint = pool.staking_mint == governing_token_mint @ ErrorCode::InvalidGoverningTokenMint,
+    )]
+    pool: Box<Account<'info, Pool>>,
+    #[account(
+        init,
+        payer = owner,
+        seeds = [
+            VOTER_WEIGHT_RECORD_PDA_SEED,
+            pool.to_account_info().key.as_ref(),
+            owner.key.as_ref(),
+        ],
+        bump,
+        space = 8 + 32 * 3 + 8 + (1 + 8) + (1 + 1) + (1 + 32) + 8
+    )]
+    voter_weight_record: Box<Account<'info, VoterWeightRecord>>,
+    #[account(mut)]
+    owner: Signer<'info>,
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateVoterWeightRecord<'info> {
+    pool: Box<Account<'info, Pool>>,
+    #[account(
+        has_one = owner,
+        has_one = pool,
+        seeds = [
+            owner.key.as_ref(),
+            pool.to_account_info().key.as_ref()
+        ],
+        bump = user.nonce,
+    )]
+    user: Box<Account<'info, User>>,
+    #[account(
+        mut,
+        seeds = [
+            VOTER_WEIGHT_RECORD_PDA_SEED,
+            pool.to_account_info().key.as_ref(),
+            owner.key.as_ref(),
+        ],
+        bump,
+        constraint = voter_weight_record.governing_token_owner == owner.key(),
+        constraint = voter_weight_record.realm == pool.realm @ ErrorCode::InvalidRealm,
+        constraint = voter_weight_record.governing_token_mint == pool.staking_mint @ ErrorCode::InvalidGoverningTokenMint,
+    )]
+    voter_weight_record: Box<Account<'info, VoterWeightRecord>>,
+    owner: Signer<'info>,
 }
 
 #[account]
@@ -1438,6 +2302,10 @@ pub struct Pool {
     pub reward_mint: Pubkey,
     /// Vault to store reward A tokens.
     pub reward_vault: Pubkey,
+    /// Token program that owns the staking mint.
+    pub staking_token_program: Pubkey,
+    /// Token program that owns the reward mint.
+    pub reward_token_program: Pubkey,
     /// The period which rewards are linearly distributed.
     pub reward_duration: u64,
     /// The timestamp at which the current reward period ends.
@@ -1446,6 +2314,18 @@ pub struct Pool {
     pub last_update_time: u64,
     /// Rate of reward distribution.
     pub reward_rate: u64,
+    /// How the pool calculates its rewards.
+    pub calculator_kind: CalculatorKind,
+    /// Annual reward rate in basis points of the total staked, for target-APR pools.
+    pub target_apr_bps: u64,
+    /// Maximum rewards a target-APR pool distributes per second.
+    pub max_emission_rate: u64,
+    /// Funded rewards that have not been distributed to stakers yet.
+    pub reward_undistributed: u64,
+    /// Rewards distributed to stakers that have not been paid out yet.
+    pub reward_owed_total: u64,
+    /// Rewards owed to claimants that the reward vault could not cover.
+    pub reward_debt_total: u64,
     /// Last calculated reward per pool token.
     pub reward_per_token_stored: u128,
     /// Users staked
@@ -1458,14 +2338,40 @@ pub struct Pool {
     pub merchant_stake_list: Vec<Pubkey>,
     /// List of the passive stakers
     pub passive_stakers_list: Vec<Pubkey>,
+    /// Scheduled reward emission phases, ordered and non-overlapping
+    pub emission_phases: Vec<EmissionPhase>,
     /// authorized funders
     /// [] because short size, fixed account size, and ease of use on
     /// client due to auto generated account size property
     pub funders: [Pubkey; 4],
+    /// Governance realm the pool's voter weight records are created for.
+    pub realm: Pubkey,
     //trailer for future use
     pub trailer: [u8; 31],
 }
 
+/// The reward economics a pool runs on
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub enum CalculatorKind {
+    /// A fixed number of tokens per second, optionally following emission phases.
+    FixedRate,
+    /// Each funding is spread linearly over `reward_duration`.
+    FundedLinear,
+    /// A fixed annual percentage of the total staked.
+    TargetApr,
+}
+
+/// A period during which the pool emits rewards at a fixed rate
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default)]
+pub struct EmissionPhase {
+    /// The timestamp at which the phase starts.
+    pub start_ts: u64,
+    /// The timestamp at which the phase ends.
+    pub end_ts: u64,
+    /// Rewards distributed per second across all stakers.
+    pub reward_rate: u64,
+}
+
 #[account]
 #[derive(Default)]
 pub struct User {
@@ -1478,11 +2384,17 @@ pub struct User {
     pub reward_per_token_complete: u128,
     /// The amount of token pending claim.
     pub reward_per_token_pending: u64,
+    /// The part of the pending claim the reward vault could not cover.
+    pub reward_debt: u64,
 
     /// The amount staked.
     pub balance_staked: u64,
     /// The amount staked.
     pub balance_self_staked: u64,
+    /// The part of the stake locked by `lock_authority`, which the user cannot unstake.
+    pub balance_locked: u64,
+    /// The signer that staked and may unstake the locked balance.
+    pub lock_authority: Pubkey,
     /// The timestamp when user created.
     pub registerd_at: i64,
     /// The timestamp when staking tokens first.
@@ -1519,6 +2431,8 @@ pub struct Merchant {
     pub reward_per_token_complete: u128,
     /// The amount of token pending claim.
     pub reward_per_token_pending: u64,
+    /// The part of the pending claim the reward vault could not cover.
+    pub reward_debt: u64,
     /// The amount staked in the main pool.
     pub self_balance_staked: u64,
     /// The amount staked.
@@ -1568,6 +2482,82 @@ pub struct MerchantUser {
     pub nonce: u8,
 }
 
+/// Voter weight of a staker, laid out as spl-governance's voter-weight add-in expects.
+/// Anchor's discriminator for an account named `VoterWeightRecord` is the one the add-in interface uses.
+#[account]
+pub struct VoterWeightRecord {
+    /// The realm the voter weight applies to.
+    pub realm: Pubkey,
+    /// Governing token mint of the realm the weight is counted for.
+    pub governing_token_mint: Pubkey,
+    /// The staker the weight belongs to.
+    pub governing_token_owner: Pubkey,
+    /// Voting power derived from the staked positions.
+    pub voter_weight: u64,
+    /// The slot at which the weight stops being valid.
+    pub voter_weight_expiry: Option<u64>,
+    /// The governance action the weight was evaluated for.
+    pub weight_action: Option<VoterWeightAction>,
+    /// The governance account the weight was evaluated for.
+    pub weight_action_target: Option<Pubkey>,
+    /// Reserved by the add-in interface.
+    pub reserved: [u8; 8],
+}
+
+/// Governance actions a voter weight can be restricted to
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub enum VoterWeightAction {
+    CastVote,
+    CommentProposal,
+    CreateGovernance,
+    CreateProposal,
+    SignOffProposal,
+}
+
+#[event]
+pub struct RewardClaimed {
+    pub pool: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    /// The pool's current reward rate annualised, in basis points of the total staked.
+    pub effective_apr_bps: u64,
+}
+
+#[event]
+pub struct RewardClaimPartiallyFilled {
+    pub pool: Pubkey,
+    /// The user or merchant account that claimed.
+    pub claimant: Pubkey,
+    pub requested: u64,
+    pub paid: u64,
+    /// The part of the claim that stays pending until the pool is topped up.
+    pub unpaid: u64,
+    pub underfunded_by: u64,
+}
+
+#[event]
+pub struct RewardShortfallReported {
+    pub pool: Pubkey,
+    pub reward_owed_total: u64,
+    pub reward_debt_total: u64,
+    pub reward_vault_balance: u64,
+    pub underfunded_by: u64,
+}
+
+#[event]
+pub struct PendingRewardReported {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub pending_reward: u64,
+}
+
+#[event]
+pub struct EffectiveAprReported {
+    pub pool: Pubkey,
+    pub total_staked: u64,
+    pub effective_apr_bps: u64,
+}
+
 #[error]
 pub enum ErrorCode {
     #[msg("Insufficient funds to unstake.")]
@@ -1597,5 +2587,31 @@ pub enum ErrorCode {
     #[msg("No tokens to withdraw")]
     NoTokensToWithdraw,
     #[msg("Not time to withdraw tokens")]
-    NotTimeToWithdrawTokens
+    NotTimeToWithdrawTokens,
+    #[msg("Emission phase must end after it starts.")]
+    InvalidEmissionPhase,
+    #[msg("Emission phase cannot start in the past.")]
+    EmissionPhaseInPast,
+    #[msg("Emission phase overlaps the previous phase.")]
+    EmissionPhaseOverlap,
+    #[msg("Maximum emission phases already scheduled.")]
+    MaxEmissionPhases,
+    #[msg("Target APR and max emission rate must be greater than zero on a target-APR pool.")]
+    InvalidTargetApr,
+    #[msg("Locked stake belongs to another lock authority.")]
+    LockAuthorityMismatch,
+    #[msg("Not the token program of the mint.")]
+    InvalidTokenProgram,
+    #[msg("Mint has an extension the pool cannot account for.")]
+    UnsupportedMintExtension,
+    #[msg("User has rewards pending, claim them before closing.")]
+    RewardsPending,
+    #[msg("Clock timestamp is before the unix epoch.")]
+    InvalidTimestamp,
+    #[msg("Realm is not the one configured for the pool.")]
+    InvalidRealm,
+    #[msg("Governing token mint is not the pool's staking mint.")]
+    InvalidGoverningTokenMint,
+    #[msg("The user has to sign to stake locked tokens.")]
+    OwnerMustSign,
 }
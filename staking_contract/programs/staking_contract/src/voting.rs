@@ -0,0 +1,52 @@
+use crate::*;
+
+/// Calculates the time-weighted voting power of a user at `now`.
+///
+/// Every staked position counts with its amount plus a lock bonus: self-staked tokens
+/// are locked until `end_ts`, and each on-behalf grant that has not been withdrawn is
+/// locked for `LOCKING_PERIOD_OF_STAKING_ON_BEHALF` after it was staked. Locked stake
+/// counts without a bonus, as its lock authority can take it back at any time.
+pub fn voting_power(user: &User, now: i64) -> u64 {
+    let mut power = position_voting_power(user.balance_self_staked, user.end_ts, now);
+    power = power.saturating_add(position_voting_power(user.balance_locked, now, now));
+
+    for i in 0..user.behalf_staked_amount.len() {
+        if user.behalf_claimed_status[i] {
+            continue;
+        }
+
+        let lock_end_ts = user.behalf_staked_ts[i]
+            .saturating_add(LOCKING_PERIOD_OF_STAKING_ON_BEHALF);
+        power = power.saturating_add(position_voting_power(
+            user.behalf_staked_amount[i],
+            lock_end_ts,
+            now,
+        ));
+    }
+
+    power
+}
+
+/// The staked amount plus a bonus that grows linearly with the remaining lock time,
+/// reaching `MAX_LOCK_BONUS_BPS` of the amount at `MAX_VOTING_LOCK_PERIOD` or more.
+fn position_voting_power(amount: u64, lock_end_ts: i64, now: i64) -> u64 {
+    let lock_remaining = lock_end_ts
+        .saturating_sub(now)
+        .clamp(0, MAX_VOTING_LOCK_PERIOD);
+
+    let bonus = (amount as u128)
+        .checked_mul(MAX_LOCK_BONUS_BPS as u128)
+        .unwrap()
+        .checked_mul(lock_remaining as u128)
+        .unwrap()
+        .checked_div(BPS_DENOMINATOR as u128)
+        .unwrap()
+        .checked_div(MAX_VOTING_LOCK_PERIOD as u128)
+        .unwrap();
+
+    (amount as u128)
+        .checked_add(bonus)
+        .unwrap()
+        .try_into()
+        .unwrap_or(u64::MAX)
+}
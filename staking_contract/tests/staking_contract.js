@@ -66,6 +66,134 @@ describe('Multiuser Reward Pool', () => {
     await mainPoolCreator.initializeMainPool(mainPoolKeypair, rewardDuration);
   });
 
+  it("Creates pools with other reward calculators", async () => {
+    let creator = new User(1000);
+    await creator.init(10_000_000_000, bindPubkey, 10_000_000_000_000);
+
+    await creator.initializeMainPool(anchor.web3.Keypair.generate(), rewardDuration, { fundedLinear: {} });
+    await creator.fund(1_000_000_000);
+
+    let poolObject = await creator.program.account.pool.fetch(creator.poolPubkey);
+    assert.ok(poolObject.calculatorKind.fundedLinear);
+    assert.equal(poolObject.rewardRate.toNumber(), 1_000_000_000 / rewardDuration.toNumber());
+
+    await creator.initializeMainPool(anchor.web3.Keypair.generate(), rewardDuration, { targetApr: {} }, 1_000, 1_000_000);
+    poolObject = await creator.program.account.pool.fetch(creator.poolPubkey);
+    assert.ok(poolObject.calculatorKind.targetApr);
+    assert.equal(poolObject.targetAprBps.toNumber(), 1_000);
+
+    await creator.setTargetApr(1_500, 2_000_000);
+    poolObject = await creator.program.account.pool.fetch(creator.poolPubkey);
+    assert.equal(poolObject.targetAprBps.toNumber(), 1_500);
+    assert.equal(poolObject.maxEmissionRate.toNumber(), 2_000_000);
+
+    // a target-APR pool without an emission cap would never pay out
+    await assert.rejects(creator.setTargetApr(1_500, 0));
+    await assert.rejects(
+      creator.initializeMainPool(anchor.web3.Keypair.generate(), rewardDuration, { targetApr: {} }, 1_000, 0)
+    );
+  });
+
+  it("Caps target-APR emission by what the reward vault holds", async () => {
+    let creator = new User(1003);
+    await creator.init(10_000_000_000, bindPubkey, 10_000_000_000_000);
+    await creator.initializeMainPool(anchor.web3.Keypair.generate(), rewardDuration, { targetApr: {} }, 1_000, 1_000_000_000);
+
+    let staker = new User(1004);
+    await staker.init(10_000_000_000, bindPubkey, 5_000_000_000);
+    await staker.createUserStakingAccount(creator.poolPubkey);
+    await staker.stakeTokens(5_000_000_000);
+
+    // nothing to pay from, so nothing accrues and the APR reads 0 without anyone claiming
+    await delay(2000);
+    assert.equal((await creator.effectiveApr()).toNumber(), 0);
+    assert.equal((await staker.pendingReward()).toNumber(), 0);
+
+    // the target pays about 15 a second, so this lasts a few seconds from the funding on
+    await creator.fund(100);
+    assert.equal((await creator.effectiveApr()).toNumber(), 1_000);
+
+    // and emission stops once the vault is owed in full
+    await delay(8000);
+    let pending = await staker.pendingReward();
+    assert.ok(pending.gtn(0) && pending.lten(100));
+    assert.equal((await creator.effectiveApr()).toNumber(), 0);
+  });
+
+  it("Schedules reward emission phases", async () => {
+    const now = Math.floor(Date.now() / 1000);
+    await mainPoolCreator.addEmissionPhase(now + 5, now + 3600, 10_000_000);
+    await mainPoolCreator.addEmissionPhase(now + 3600, now + 7200, 5_000_000);
+
+    // phases are append-only and cannot overlap earlier ones
+    await assert.rejects(
+      mainPoolCreator.addEmissionPhase(now + 3000, now + 9000, 1_000_000)
+    );
+
+    let poolObject = await mainPoolCreator.program.account.pool.fetch(mainPoolCreator.poolPubkey);
+    assert.equal(poolObject.emissionPhases.length, 2);
+    assert.equal(poolObject.emissionPhases[1].rewardRate.toNumber(), 5_000_000);
+  });
+
+  it("Keeps rewards accrued before an emission phase is added", async () => {
+    let creator = new User(1001);
+    await creator.init(10_000_000_000, bindPubkey, 10_000_000_000_000);
+    await creator.initializeMainPool(anchor.web3.Keypair.generate(), rewardDuration);
+
+    let staker = new User(1002);
+    await staker.init(10_000_000_000, bindPubkey, 5_000_000_000);
+    await staker.createUserStakingAccount(creator.poolPubkey);
+    await staker.stakeTokens(100_000_000);
+
+    // accrues at the fixed rate until the first phase is scheduled
+    await delay(2000);
+    let accruedBefore = await staker.pendingReward();
+    assert.ok(accruedBefore.gtn(0));
+
+    const now = Math.floor(Date.now() / 1000);
+    await creator.addEmissionPhase(now + 2, now + 3600, 10_000_000);
+
+    let poolObject = await creator.program.account.pool.fetch(creator.poolPubkey);
+    assert.ok(poolObject.lastUpdateTime.gten(now - 1));
+
+    let settled = await staker.pendingReward();
+    assert.ok(settled.gte(accruedBefore));
+
+    // and then at the phase's rate once it starts
+    await delay(4000);
+    let accruedAfter = await staker.pendingReward();
+    assert.ok(accruedAfter.gt(settled));
+  });
+
+  it("Emits nothing after the last emission phase and drops ended phases", async () => {
+    let creator = new User(1005);
+    await creator.init(10_000_000_000, bindPubkey, 10_000_000_000_000);
+    await creator.initializeMainPool(anchor.web3.Keypair.generate(), rewardDuration);
+
+    let staker = new User(1006);
+    await staker.init(10_000_000_000, bindPubkey, 5_000_000_000);
+    await staker.createUserStakingAccount(creator.poolPubkey);
+    await staker.stakeTokens(100_000_000);
+
+    let now = Math.floor(Date.now() / 1000);
+    await creator.addEmissionPhase(now + 2, now + 5, 10_000_000);
+
+    // once the schedule has run out the pool does not fall back to the fixed rate
+    await delay(7000);
+    let accruedAtEnd = await staker.pendingReward();
+    assert.ok(accruedAtEnd.gtn(0));
+    await delay(2000);
+    let accruedLater = await staker.pendingReward();
+    assert.ok(accruedLater.eq(accruedAtEnd));
+
+    // the ended phase makes room for the next one
+    now = Math.floor(Date.now() / 1000);
+    await creator.addEmissionPhase(now + 2, now + 3600, 1_000_000);
+    let poolObject = await creator.program.account.pool.fetch(creator.poolPubkey);
+    assert.equal(poolObject.emissionPhases.length, 1);
+    assert.equal(poolObject.emissionPhases[0].rewardRate.toNumber(), 1_000_000);
+  });
+
   it('User does some single staking', async () => {
 
     //we test all this in greater detail later, but this is a flow for single reward staking
@@ -78,9 +206,28 @@ describe('Multiuser Reward Pool', () => {
 
     await mainPoolCreator.fund(1_000_000_000);
 
+    let pending = await user.pendingReward();
+    let userObject = await user.program.account.user.fetch(user.userPubkey);
+    assert.ok(pending.gte(userObject.rewardPerTokenPending));
+
     await user.claim();
 
+    await mainPoolCreator.rewardShortfall();
+    let poolObject = await user.program.account.pool.fetch(pool);
+    assert.equal(poolObject.rewardDebtTotal.toNumber(), 0);
+
+    // what accrued up to the unstake stays pending, and closing would forfeit it
     await user.unstakeTokens(100_000_000);
+    userObject = await user.program.account.user.fetch(user.userPubkey);
+    assert.ok(userObject.rewardPerTokenPending.gtn(0));
+    await assert.rejects(user.closeUser());
+
+    // with nothing staked no more accrues, so the staking-client preview
+    // (`pending_user_reward`) is exactly the pending reward stored on the user
+    pending = await user.pendingReward();
+    assert.ok(pending.eq(userObject.rewardPerTokenPending));
+
+    await user.claim();
     await user.closeUser();
   });
 
@@ -156,6 +303,79 @@ describe('Multiuser Reward Pool', () => {
     await delay(2000);
     await user.withdrawToken()
   });
+
+  ///////////////////////////////////////////////////////
+  /// Locked stake
+  ///////////////////////////////////////////////////////
+  it('Lock authority stakes for a user who cannot unstake it', async () => {
+
+    let pool = mainPoolCreator.poolPubkey;
+    let user = new User(400);
+    await user.init(10_000_000_000, bindPubkey, 0);
+    await user.createUserStakingAccount(pool);
+
+    let lockAuthority = new User(401);
+    await lockAuthority.init(10_000_000_000, bindPubkey, 5_000_000_000);
+
+    // without the user's signature no one can make themselves its lock authority
+    await assert.rejects(lockAuthority.program.rpc.stakeLocked(
+      new anchor.BN(1),
+      { accounts: await lockAuthority.lockedStakeAccounts(user) }
+    ));
+
+    await lockAuthority.stakeLocked(user, 100_000_000);
+
+    let userObject = await user.program.account.user.fetch(user.userPubkey);
+    assert.equal(userObject.balanceStaked.toNumber(), 100_000_000);
+    assert.equal(userObject.balanceLocked.toNumber(), 100_000_000);
+    assert.ok(userObject.lockAuthority.equals(lockAuthority.pubkey));
+
+    // the locked position is not the user's to unstake
+    await assert.rejects(user.unstakeTokens(100_000_000));
+
+    await lockAuthority.unstakeLocked(user, 100_000_000);
+    userObject = await user.program.account.user.fetch(user.userPubkey);
+    assert.equal(userObject.balanceLocked.toNumber(), 0);
+    let lockedAccount = await lockAuthority.bindTokenMint.getAccountInfo(lockAuthority.bindTokenAta);
+    assert.equal(lockedAccount.amount.toNumber(), 5_000_000_000);
+  });
+
+  ///////////////////////////////////////////////////////
+  /// Governance
+  ///////////////////////////////////////////////////////
+  it('Staker refreshes a voter weight record', async () => {
+
+    let pool = mainPoolCreator.poolPubkey;
+    let user = new User(300);
+    await user.init(10_000_000_000, bindPubkey, 5_000_000_000);
+    await user.createUserStakingAccount(pool);
+    await user.stakeTokens(100_000_000);
+
+    const realm = anchor.web3.Keypair.generate().publicKey;
+    // records can only be created once the pool authority configured a realm
+    await assert.rejects(user.createVoterWeightRecord(realm, bindPubkey));
+    await mainPoolCreator.setRealm(realm);
+
+    // and only for that realm and the pool's staking mint
+    await assert.rejects(user.createVoterWeightRecord(anchor.web3.Keypair.generate().publicKey, bindPubkey));
+    await assert.rejects(user.createVoterWeightRecord(realm, anchor.web3.Keypair.generate().publicKey));
+
+    await user.createVoterWeightRecord(realm, bindPubkey);
+    let record = await user.updateVoterWeightRecord();
+
+    assert.ok(record.realm.equals(realm));
+    assert.ok(record.governingTokenMint.equals(bindPubkey));
+    assert.ok(record.governingTokenOwner.equals(user.pubkey));
+    // unlocked stake counts without a bonus
+    assert.equal(record.voterWeight.toNumber(), 100_000_000);
+
+    // locked stake, e.g. of a vesting grant, counts without a bonus too
+    let lockAuthority = new User(301);
+    await lockAuthority.init(10_000_000_000, bindPubkey, 5_000_000_000);
+    await lockAuthority.stakeLocked(user, 50_000_000);
+    record = await user.updateVoterWeightRecord();
+    assert.equal(record.voterWeight.toNumber(), 150_000_000);
+  });
 });
 
 function delay(ms) {
@@ -37,7 +37,7 @@ class User {
         }
     }
 
-    async initializeMainPool(poolKeypair, rewardDuration) {
+    async initializeMainPool(poolKeypair, rewardDuration, calculatorKind = { fixedRate: {} }, targetAprBps = 0, maxEmissionRate = 0) {
         const [poolSigner, bump] = await anchor.web3.PublicKey.findProgramAddress(
             [poolKeypair.publicKey.toBuffer()],
             this.program.programId
@@ -71,6 +71,9 @@ class User {
         await this.program.rpc.initializeMainPool(
             bump,
             rewardDuration,
+            calculatorKind,
+            new anchor.BN(targetAprBps),
+            new anchor.BN(maxEmissionRate),
             {
                 accounts: {
                     authority: this.provider.wallet.publicKey,
@@ -81,7 +84,8 @@ class User {
                     poolSigner: poolSigner,
                     pool: this.poolPubkey,
                     poolTokenAta: poolTokenAta.address,
-                    tokenProgram: TOKEN_PROGRAM_ID,
+                    stakingTokenProgram: TOKEN_PROGRAM_ID,
+                    rewardTokenProgram: TOKEN_PROGRAM_ID,
                     rent: anchor.web3.SYSVAR_RENT_PUBKEY,
                     systemProgram: anchor.web3.SystemProgram.programId,
                 },
@@ -91,6 +95,53 @@ class User {
 
     }
 
+    async setTargetApr(targetAprBps, maxEmissionRate) {
+        let poolObject = await this.program.account.pool.fetch(this.poolPubkey);
+
+        await this.program.rpc.setTargetApr(
+            new anchor.BN(targetAprBps),
+            new anchor.BN(maxEmissionRate),
+            {
+                accounts: {
+                    pool: this.poolPubkey,
+                    authority: this.provider.wallet.publicKey,
+                    stakingVault: poolObject.stakingVault,
+                    rewardVault: poolObject.rewardVault,
+                },
+            }
+        );
+    }
+
+    async setRealm(realm) {
+        await this.program.rpc.setRealm(
+            realm,
+            {
+                accounts: {
+                    pool: this.poolPubkey,
+                    authority: this.provider.wallet.publicKey,
+                },
+            }
+        );
+    }
+
+    async addEmissionPhase(startTs, endTs, rewardRate) {
+        let poolObject = await this.program.account.pool.fetch(this.poolPubkey);
+
+        await this.program.rpc.addEmissionPhase(
+            new anchor.BN(startTs),
+            new anchor.BN(endTs),
+            new anchor.BN(rewardRate),
+            {
+                accounts: {
+                    pool: this.poolPubkey,
+                    authority: this.provider.wallet.publicKey,
+                    stakingVault: poolObject.stakingVault,
+                    rewardVault: poolObject.rewardVault,
+                },
+            }
+        );
+    }
+
     async createUserStakingAccount(poolPubkey) {
         this.poolPubkey = poolPubkey;
 
@@ -146,6 +197,8 @@ class User {
                     // Stake instance.
                     pool: this.poolPubkey,
                     stakingVault: poolObject.stakingVault,
+                    rewardVault: poolObject.rewardVault,
+                    stakingMint: poolObject.stakingMint,
                     // User.
                     user: this.userPubkey,
                     owner: this.provider.wallet.publicKey,
@@ -225,6 +278,8 @@ class User {
                     // Stake instance.
                     pool: this.poolPubkey,
                     stakingVault: poolObject.stakingVault,
+                    rewardVault: poolObject.rewardVault,
+                    stakingMint: poolObject.stakingMint,
                     // User.
                     user: this.userPubkey,
                     owner: this.provider.wallet.publicKey,
@@ -238,6 +293,56 @@ class User {
             });
     }
 
+    // Stakes from this user's token account for `staker`, with this user as the lock authority
+    async stakeLocked(staker, amount) {
+        await this.program.rpc.stakeLocked(
+            new anchor.BN(amount),
+            {
+                accounts: await this.lockedStakeAccounts(staker),
+                signers: [staker.keypair],
+            }
+        );
+    }
+
+    async unstakeLocked(staker, amount) {
+        await this.program.rpc.unstakeLocked(
+            new anchor.BN(amount),
+            {
+                accounts: await this.lockedStakeAccounts(staker),
+            }
+        );
+    }
+
+    async lockedStakeAccounts(staker) {
+        let poolObject = await this.program.account.pool.fetch(staker.poolPubkey);
+
+        const [
+            _poolSigner,
+            _nonce,
+        ] = await anchor.web3.PublicKey.findProgramAddress(
+            [staker.poolPubkey.toBuffer()],
+            this.program.programId
+        );
+
+        return {
+            // Stake instance.
+            pool: staker.poolPubkey,
+            stakingVault: poolObject.stakingVault,
+            rewardVault: poolObject.rewardVault,
+            stakingMint: poolObject.stakingMint,
+            // User.
+            user: staker.userPubkey,
+            owner: staker.pubkey,
+            // Locked tokens.
+            lockAuthority: this.provider.wallet.publicKey,
+            lockedTokenAccount: this.bindTokenAta,
+            // Program signers.
+            poolSigner: _poolSigner,
+            // Misc.
+            tokenProgram: TOKEN_PROGRAM_ID,
+        };
+    }
+
     async fund(amount, poolPubkey) {
         let pubkeyToUse = poolPubkey ?? this.poolPubkey;
         let poolObject = await this.program.account.pool.fetch(pubkeyToUse);
@@ -259,6 +364,7 @@ class User {
                     pool: pubkeyToUse,
                     stakingVault: poolObject.stakingVault,
                     rewardVault: poolObject.rewardVault,
+                    rewardMint: poolObject.rewardMint,
                     funder: this.provider.wallet.publicKey,
                     from: this.bindTokenAta,
                     // Program signers.
@@ -291,6 +397,7 @@ class User {
                     pool: this.poolPubkey,
                     stakingVault: poolObject.stakingVault,
                     rewardVault: poolObject.rewardVault,
+                    rewardMint: poolObject.rewardMint,
                     // User.
                     user: this.userPubkey,
                     owner: this.provider.wallet.publicKey,
@@ -306,6 +413,63 @@ class User {
 
     }
 
+    async rewardShortfall() {
+        let poolObject = await this.program.account.pool.fetch(this.poolPubkey);
+
+        const tx = await this.program.rpc.rewardShortfall({
+            accounts: {
+                pool: this.poolPubkey,
+                stakingVault: poolObject.stakingVault,
+                rewardVault: poolObject.rewardVault,
+            },
+        });
+        return tx;
+    }
+
+    async pendingReward() {
+        let poolObject = await this.program.account.pool.fetch(this.poolPubkey);
+
+        const { events, raw } = await this.program.simulate.pendingReward({
+            accounts: {
+                pool: this.poolPubkey,
+                stakingVault: poolObject.stakingVault,
+                rewardVault: poolObject.rewardVault,
+                user: this.userPubkey,
+            },
+        });
+
+        // the return data is logged as "Program return: <program id> <base64 data>"
+        const prefix = `Program return: ${this.program.programId.toBase58()} `;
+        const returned = raw.find((log) => log.startsWith(prefix));
+        const pendingReward = new anchor.BN(Buffer.from(returned.slice(prefix.length), 'base64'), 'le');
+        if (!pendingReward.eq(events[0].data.pendingReward)) {
+            throw new Error("pending reward return data does not match the event");
+        }
+
+        return pendingReward;
+    }
+
+    async effectiveApr() {
+        let poolObject = await this.program.account.pool.fetch(this.poolPubkey);
+
+        const { events, raw } = await this.program.simulate.effectiveApr({
+            accounts: {
+                pool: this.poolPubkey,
+                stakingVault: poolObject.stakingVault,
+                rewardVault: poolObject.rewardVault,
+            },
+        });
+
+        const prefix = `Program return: ${this.program.programId.toBase58()} `;
+        const returned = raw.find((log) => log.startsWith(prefix));
+        const effectiveAprBps = new anchor.BN(Buffer.from(returned.slice(prefix.length), 'base64'), 'le');
+        if (!effectiveAprBps.eq(events[0].data.effectiveAprBps)) {
+            throw new Error("effective APR return data does not match the event");
+        }
+
+        return effectiveAprBps;
+    }
+
     async closeUser() {
         await this.program.rpc.closeUser(
             {
@@ -340,6 +504,7 @@ class User {
                     pool: this.poolPubkey,
                     stakingVault: poolObject.stakingVault,
                     rewardVault: poolObject.rewardVault,
+                    stakingMint: poolObject.stakingMint,
                     poolSigner,
                     tokenProgram: TOKEN_PROGRAM_ID,
                 },
@@ -444,6 +609,8 @@ class User {
                     pool: this.poolPubkey,
                     merchant: this.merchantPubkey,
                     stakingVault: poolObject.stakingVault,
+                    rewardVault: poolObject.rewardVault,
+                    stakingMint: poolObject.stakingMint,
                     // User.
                     merchantUser: this.userPubkey,
                     owner: this.provider.wallet.publicKey,
@@ -481,6 +648,8 @@ class User {
                     pool: this.poolPubkey,
                     merchant: this.merchantPubkey,
                     stakingVault: poolObject.stakingVault,
+                    rewardVault: poolObject.rewardVault,
+                    stakingMint: poolObject.stakingMint,
                     // User.
                     merchantUser: this.userPubkey,
                     owner: this.provider.wallet.publicKey,
@@ -561,6 +730,7 @@ class User {
                 merchant: this.merchantPubkey,
                 stakingVault: poolObject.stakingVault,
                 rewardVault: poolObject.rewardVault,
+                rewardMint: poolObject.rewardMint,
                 owner: this.provider.wallet.publicKey,
                 rewardAccount: this.bindTokenAta,
                 // Program signers.
@@ -690,6 +860,8 @@ class User {
                     // Stake instance.
                     pool: this.poolPubkey,
                     stakingVault: poolObject.stakingVault,
+                    rewardVault: poolObject.rewardVault,
+                    stakingMint: poolObject.stakingMint,
                     // User.
                     user: _userPubkey,
                     owner: this.provider.wallet.publicKey,
@@ -729,6 +901,8 @@ class User {
                     // Stake instance.
                     pool: this.poolPubkey,
                     stakingVault: poolObject.stakingVault,
+                    rewardVault: poolObject.rewardVault,
+                    stakingMint: poolObject.stakingMint,
                     // User.
                     user: this.userPubkey,
                     owner: this.provider.wallet.publicKey,
@@ -744,6 +918,43 @@ class User {
         let userInfo = await this.program.account.user.fetch(this.userPubkey);
         console.log("===============userInfoAfterWithdrawn=================", userInfo)
     }
+
+    async createVoterWeightRecord(realm, governingTokenMint) {
+        const [_voterWeightRecord] = await anchor.web3.PublicKey.findProgramAddress(
+            [
+                Buffer.from(anchor.utils.bytes.utf8.encode('voter-weight-record')),
+                this.poolPubkey.toBuffer(),
+                this.provider.wallet.publicKey.toBuffer(),
+            ],
+            this.program.programId
+        );
+        this.voterWeightRecordPubkey = _voterWeightRecord;
+
+        await this.program.rpc.createVoterWeightRecord(
+            realm,
+            governingTokenMint,
+            {
+                accounts: {
+                    pool: this.poolPubkey,
+                    voterWeightRecord: this.voterWeightRecordPubkey,
+                    owner: this.provider.wallet.publicKey,
+                    systemProgram: anchor.web3.SystemProgram.programId,
+                },
+            });
+    }
+
+    async updateVoterWeightRecord() {
+        await this.program.rpc.updateVoterWeightRecord({
+            accounts: {
+                pool: this.poolPubkey,
+                user: this.userPubkey,
+                voterWeightRecord: this.voterWeightRecordPubkey,
+                owner: this.provider.wallet.publicKey,
+            },
+        });
+
+        return await this.program.account.voterWeightRecord.fetch(this.voterWeightRecordPubkey);
+    }
 }
 
 module.exports = {
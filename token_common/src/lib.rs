@@ -0,0 +1,253 @@
+//! Mints and token accounts of either the SPL Token program or Token-2022, as the staking and
+//! vesting programs see them. Their Anchor versions cannot deserialize Token-2022 accounts, so
+//! both take them as plain `AccountInfo`s; the helpers here parse the base state, screen mint
+//! extensions and issue the token CPIs. Nothing here depends on Anchor, so the two programs can
+//! share it while pinning different Anchor versions.
+
+use solana_program::account_info::AccountInfo;
+use solana_program::entrypoint::ProgramResult;
+use solana_program::instruction::{AccountMeta, Instruction};
+use solana_program::program::{get_return_data, invoke, invoke_signed};
+use solana_program::program_error::ProgramError;
+use solana_program::program_pack::Pack;
+use solana_program::pubkey::Pubkey;
+use spl_token::state::{Account as TokenAccount, Mint, Multisig};
+
+pub mod token_2022 {
+    solana_program::declare_id!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+}
+
+/// Byte 165, where an account with extensions stores its `AccountType`: Token-2022 keeps the
+/// SPL Token layout for the base state and pads mints to the token account length so both
+/// kinds share it (`BASE_ACCOUNT_LENGTH` in spl-token-2022's `extension/mod.rs`)
+const ACCOUNT_TYPE_OFFSET: usize = TokenAccount::LEN;
+/// `AccountType::Mint` and `AccountType::Account` in spl-token-2022's `extension/mod.rs`
+const ACCOUNT_TYPE_MINT: u8 = 1;
+const ACCOUNT_TYPE_ACCOUNT: u8 = 2;
+/// Byte 166, after the one-byte account type, where the extensions start as
+/// (type: u16, length: u16, value) entries
+const EXTENSIONS_OFFSET: usize = ACCOUNT_TYPE_OFFSET + 1;
+/// The type and length that lead each extension entry
+const EXTENSION_HEADER_LEN: usize = 4;
+/// `TokenInstruction::GetAccountDataSize` in spl-token-2022's `instruction.rs`
+const GET_ACCOUNT_DATA_SIZE: u8 = 21;
+/// `TokenInstruction::TransferFeeExtension` in spl-token-2022's `instruction.rs`, and its
+/// `HarvestWithheldTokensToMint` in `extension/transfer_fee/instruction.rs`
+const TRANSFER_FEE_EXTENSION: u8 = 26;
+const HARVEST_WITHHELD_TOKENS_TO_MINT: u8 = 4;
+
+/// Discriminants of spl-token-2022's `ExtensionType`, in `extension/mod.rs`
+const EXTENSION_TRANSFER_FEE_AMOUNT: u16 = 2;
+const EXTENSION_CONFIDENTIAL_TRANSFER_MINT: u16 = 4;
+const EXTENSION_NON_TRANSFERABLE: u16 = 9;
+const EXTENSION_PERMANENT_DELEGATE: u16 = 12;
+const EXTENSION_TRANSFER_HOOK: u16 = 14;
+
+/// Mint extensions that let tokens move or stay put behind a program's back, so its books
+/// would drift from its vaults. Transfer hooks also need accounts `transfer_checked` does not
+/// pass.
+const UNSUPPORTED_MINT_EXTENSIONS: [u16; 4] = [
+    EXTENSION_CONFIDENTIAL_TRANSFER_MINT,
+    EXTENSION_NON_TRANSFERABLE,
+    EXTENSION_PERMANENT_DELEGATE,
+    EXTENSION_TRANSFER_HOOK,
+];
+
+/// Whether `key` is the SPL Token program or Token-2022
+pub fn is_token_program(key: &Pubkey) -> bool {
+    *key == spl_token::ID || *key == token_2022::ID
+}
+
+/// The base state of a token account of either token program
+pub fn token_account(info: &AccountInfo) -> Result<TokenAccount, ProgramError> {
+    let data = info.try_borrow_data()?;
+    if !is_token_program(info.owner) || !has_type(&data, TokenAccount::LEN, ACCOUNT_TYPE_ACCOUNT) {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    TokenAccount::unpack(&data[..TokenAccount::LEN])
+}
+
+/// The base state of a mint of either token program
+pub fn mint(info: &AccountInfo) -> Result<Mint, ProgramError> {
+    let data = info.try_borrow_data()?;
+    if !is_token_program(info.owner) || !has_type(&data, Mint::LEN, ACCOUNT_TYPE_MINT) {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    Mint::unpack(&data[..Mint::LEN])
+}
+
+/// Whether `info` is a mint without extensions a vault cannot account for
+pub fn is_supported_mint(info: &AccountInfo) -> Result<bool, ProgramError> {
+    mint(info)?;
+    Ok(extension_types(&info.try_borrow_data()?)
+        .iter()
+        .all(|extension_type| !UNSUPPORTED_MINT_EXTENSIONS.contains(extension_type)))
+}
+
+/// The types of the extensions in the data of a Token-2022 account
+fn extension_types(data: &[u8]) -> Vec<u16> {
+    let mut types = Vec::new();
+    let mut offset = EXTENSIONS_OFFSET;
+    while offset + EXTENSION_HEADER_LEN <= data.len() {
+        let extension_type = u16::from_le_bytes([data[offset], data[offset + 1]]);
+        // the rest of the account is unused space
+        if extension_type == 0 {
+            break;
+        }
+
+        let length = u16::from_le_bytes([data[offset + 2], data[offset + 3]]) as usize;
+        types.push(extension_type);
+        offset += EXTENSION_HEADER_LEN + length;
+    }
+
+    types
+}
+
+/// Whether `data` is a base account of `base_len` bytes, or one with extensions of `account_type`.
+/// Token-2022 never sizes an account like a multisig, which would make its type ambiguous.
+fn has_type(data: &[u8], base_len: usize, account_type: u8) -> bool {
+    data.len() == base_len
+        || (data.len() > ACCOUNT_TYPE_OFFSET
+            && data.len() != Multisig::LEN
+            && data[ACCOUNT_TYPE_OFFSET] == account_type)
+}
+
+/// The size of a token account of `mint`, with room for the extensions the mint requires
+pub fn account_space<'info>(
+    mint: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+) -> Result<usize, ProgramError> {
+    if *token_program.key == spl_token::ID {
+        return Ok(TokenAccount::LEN);
+    }
+
+    let instruction = Instruction {
+        program_id: *token_program.key,
+        accounts: vec![AccountMeta::new_readonly(*mint.key, false)],
+        data: vec![GET_ACCOUNT_DATA_SIZE],
+    };
+    invoke(&instruction, &[mint.clone(), token_program.clone()])?;
+
+    match get_return_data() {
+        Some((program_id, data)) if program_id == *token_program.key && data.len() == 8 => {
+            let mut size = [0u8; 8];
+            size.copy_from_slice(&data);
+            Ok(u64::from_le_bytes(size) as usize)
+        }
+        _ => Err(ProgramError::InvalidAccountData),
+    }
+}
+
+/// Moves the transfer fees withheld in `account` to its mint, where the mint's withdraw
+/// authority can collect them. Token-2022 refuses to close an account still withholding fees.
+/// Anyone may harvest, so no signature is needed.
+pub fn harvest_withheld_tokens_to_mint<'info>(
+    token_program: AccountInfo<'info>,
+    mint: AccountInfo<'info>,
+    account: AccountInfo<'info>,
+) -> ProgramResult {
+    let withholds_fees =
+        extension_types(&account.try_borrow_data()?).contains(&EXTENSION_TRANSFER_FEE_AMOUNT);
+    if !withholds_fees {
+        return Ok(());
+    }
+
+    let instruction = Instruction {
+        program_id: *token_program.key,
+        accounts: vec![
+            AccountMeta::new(*mint.key, false),
+            AccountMeta::new(*account.key, false),
+        ],
+        data: vec![TRANSFER_FEE_EXTENSION, HARVEST_WITHHELD_TOKENS_TO_MINT],
+    };
+    invoke(&instruction, &[mint, account, token_program])
+}
+
+/// The accounts of a `TransferChecked` instruction
+pub struct TransferChecked<'info> {
+    pub from: AccountInfo<'info>,
+    pub mint: AccountInfo<'info>,
+    pub to: AccountInfo<'info>,
+    pub authority: AccountInfo<'info>,
+}
+
+/// Moves `amount` tokens of a mint with `decimals` decimals. With a Token-2022 transfer fee the
+/// destination is credited less than `amount`.
+pub fn transfer_checked<'info>(
+    token_program: AccountInfo<'info>,
+    accounts: TransferChecked<'info>,
+    amount: u64,
+    decimals: u8,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    let mut instruction = spl_token::instruction::transfer_checked(
+        &spl_token::ID,
+        accounts.from.key,
+        accounts.mint.key,
+        accounts.to.key,
+        accounts.authority.key,
+        &[],
+        amount,
+        decimals,
+    )?;
+    instruction.program_id = *token_program.key;
+    invoke_signed(
+        &instruction,
+        &[
+            accounts.from,
+            accounts.mint,
+            accounts.to,
+            accounts.authority,
+            token_program,
+        ],
+        signer_seeds,
+    )
+}
+
+/// Initializes `account` as a token account of `mint` held by `authority`
+pub fn initialize_account<'info>(
+    token_program: AccountInfo<'info>,
+    account: AccountInfo<'info>,
+    mint: AccountInfo<'info>,
+    authority: AccountInfo<'info>,
+    rent: AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    let mut instruction = spl_token::instruction::initialize_account(
+        &spl_token::ID,
+        account.key,
+        mint.key,
+        authority.key,
+    )?;
+    instruction.program_id = *token_program.key;
+    invoke_signed(
+        &instruction,
+        &[account, mint, authority, rent, token_program],
+        signer_seeds,
+    )
+}
+
+/// Closes the empty token `account`, sending its rent to `destination`
+pub fn close_account<'info>(
+    token_program: AccountInfo<'info>,
+    account: AccountInfo<'info>,
+    destination: AccountInfo<'info>,
+    authority: AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    let mut instruction = spl_token::instruction::close_account(
+        &spl_token::ID,
+        account.key,
+        destination.key,
+        authority.key,
+        &[],
+    )?;
+    instruction.program_id = *token_program.key;
+    invoke_signed(
+        &instruction,
+        &[account, destination, authority, token_program],
+        signer_seeds,
+    )
+}
@@ -0,0 +1,69 @@
+use std::error::Error;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::Deserialize;
+use solana_sdk::pubkey::Pubkey;
+use solana_vesting::{BatchGrant, MAX_NAME_LEN};
+
+/// One line of the grants file:
+/// `beneficiary,amount,name,start_ts,cliff_ts,duration,upfront_bps,withdraw_period,revocable`
+#[derive(Debug, Deserialize)]
+struct GrantRecord {
+    beneficiary: String,
+    amount: u64,
+    name: String,
+    start_ts: i64,
+    cliff_ts: i64,
+    duration: i64,
+    upfront_bps: u64,
+    withdraw_period: i64,
+    revocable: bool,
+}
+
+/// A grant to create for a beneficiary, as read from the grants file
+#[derive(Clone, Debug)]
+pub struct GrantRow {
+    /// Line of the file the grant was read from, for reports
+    pub line: u64,
+    pub beneficiary: Pubkey,
+    pub grant: BatchGrant,
+}
+
+/// Reads the grants file, which starts with a header line
+pub fn read_grants(path: &Path) -> Result<Vec<GrantRow>, Box<dyn Error>> {
+    let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_path(path)?;
+
+    let headers = reader.headers()?.clone();
+
+    let mut rows = Vec::new();
+    for result in reader.records() {
+        let record = result?;
+        let line = record.position().map_or(0, |position| position.line());
+        let record: GrantRecord = record
+            .deserialize(Some(&headers))
+            .map_err(|e| format!("line {}: {}", line, e))?;
+        let beneficiary = Pubkey::from_str(&record.beneficiary)
+            .map_err(|e| format!("line {}: invalid beneficiary: {}", line, e))?;
+        if record.name.len() > MAX_NAME_LEN {
+            return Err(format!("line {}: name is longer than {} bytes", line, MAX_NAME_LEN).into());
+        }
+
+        rows.push(GrantRow {
+            line,
+            beneficiary,
+            grant: BatchGrant {
+                amount: record.amount,
+                name: record.name,
+                start_ts: record.start_ts,
+                cliff_ts: record.cliff_ts,
+                duration: record.duration,
+                upfront_bps: record.upfront_bps,
+                withdraw_period: record.withdraw_period,
+                revocable: record.revocable,
+            },
+        });
+    }
+
+    Ok(rows)
+}
@@ -0,0 +1,309 @@
+//! Creates the vesting grants listed in a CSV file and reconciles them with the chain.
+//!
+//! ```text
+//! vesting-batch create <grants.csv> --mint <MINT> [--url <RPC>] [--keypair <PATH>]
+//! vesting-batch reconcile <grants.csv> --mint <MINT> [--url <RPC>] [--keypair <PATH>]
+//! ```
+//!
+//! The file starts with the header
+//! `beneficiary,amount,name,start_ts,cliff_ts,duration,upfront_bps,withdraw_period,revocable`.
+//! `create` only submits the lines that have no matching grant on chain yet, so it can be
+//! re-run after a failure. The mint may belong to the SPL Token program or Token-2022.
+
+mod grants;
+mod report;
+
+use std::env;
+use std::error::Error;
+use std::path::PathBuf;
+use std::process;
+use std::str::FromStr;
+
+use anchor_lang::{AccountDeserialize, InstructionData, ToAccountMetas};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{read_keypair_file, Keypair, Signature, Signer};
+use solana_sdk::transaction::Transaction;
+use solana_sdk::{system_program, sysvar};
+use solana_vesting::token_interface::is_token_program;
+use solana_vesting::{
+    find_investor_account, find_vault_account, find_vault_authority, find_vesting_account,
+    InvestorAccount, VestingAccount, MAX_BATCH_GRANTS,
+};
+
+use crate::grants::GrantRow;
+use crate::report::{Finding, OnChainGrant};
+
+const DEFAULT_URL: &str = "http://localhost:8899";
+/// Accounts fetched per `getMultipleAccounts` request
+const ACCOUNTS_PER_REQUEST: usize = 100;
+/// Associated token accounts created per transaction
+const ATAS_PER_TRANSACTION: usize = 8;
+
+struct Config {
+    command: String,
+    grants_path: PathBuf,
+    mint: Pubkey,
+    url: String,
+    keypair_path: PathBuf,
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
+    let config = parse_args()?;
+    let rows = grants::read_grants(&config.grants_path)?;
+    let owner = read_keypair_file(&config.keypair_path)
+        .map_err(|e| format!("{}: {}", config.keypair_path.display(), e))?;
+    let client = RpcClient::new_with_commitment(config.url.clone(), CommitmentConfig::confirmed());
+    let (investor_account, _) = find_investor_account(&owner.pubkey(), &config.mint, &solana_vesting::ID);
+
+    match config.command.as_str() {
+        "create" => {
+            let token_program = fetch_token_program(&client, &config.mint)?;
+            create(&client, &owner, &config.mint, &token_program, &investor_account, &rows)
+        }
+        "reconcile" => {
+            let findings = report::reconcile(&rows, &fetch_grants(&client, &investor_account)?);
+            print_report(&findings);
+            if !report::is_reconciled(&findings) {
+                process::exit(2);
+            }
+            Ok(())
+        }
+        command => Err(format!("unknown command {}", command).into()),
+    }
+}
+
+fn parse_args() -> Result<Config, Box<dyn Error>> {
+    let usage = "usage: vesting-batch <create|reconcile> <grants.csv> --mint <MINT> [--url <RPC>] [--keypair <PATH>]";
+    let mut args = env::args().skip(1);
+    let command = args.next().ok_or(usage)?;
+    let grants_path = PathBuf::from(args.next().ok_or(usage)?);
+
+    let mut mint = None;
+    let mut url = DEFAULT_URL.to_string();
+    let mut keypair_path = PathBuf::from(env::var("HOME").unwrap_or_default())
+        .join(".config/solana/id.json");
+    while let Some(flag) = args.next() {
+        let value = args.next().ok_or(usage)?;
+        match flag.as_str() {
+            "--mint" => mint = Some(Pubkey::from_str(&value).map_err(|e| format!("--mint: {}", e))?),
+            "--url" => url = value,
+            "--keypair" => keypair_path = PathBuf::from(value),
+            _ => return Err(usage.into()),
+        }
+    }
+
+    Ok(Config {
+        command,
+        grants_path,
+        mint: mint.ok_or(usage)?,
+        url,
+        keypair_path,
+    })
+}
+
+/// Creates the grants of every line not on chain yet, `MAX_BATCH_GRANTS` per transaction
+fn create(
+    client: &RpcClient,
+    owner: &Keypair,
+    mint: &Pubkey,
+    token_program: &Pubkey,
+    investor_account: &Pubkey,
+    rows: &[GrantRow],
+) -> Result<(), Box<dyn Error>> {
+    let findings = report::reconcile(rows, &fetch_grants(client, investor_account)?);
+    let pending: Vec<&GrantRow> = rows
+        .iter()
+        .filter(|row| {
+            findings
+                .iter()
+                .any(|finding| matches!(finding, Finding::Missing { line } if *line == row.line))
+        })
+        .collect();
+    println!("{} of {} grants to create", pending.len(), rows.len());
+
+    create_missing_atas(client, owner, mint, token_program, &pending)?;
+
+    let investor: InvestorAccount = fetch_account(client, investor_account)?;
+    let mut grant_id = investor.grant_count;
+    let (vault_authority, _) = find_vault_authority(investor_account, &solana_vesting::ID);
+
+    for chunk in pending.chunks(MAX_BATCH_GRANTS) {
+        let mut accounts = solana_vesting::accounts::InitializeBatch {
+            owner: owner.pubkey(),
+            mint: *mint,
+            owner_token_account: associated_token_address(&owner.pubkey(), mint, token_program),
+            investor_account: *investor_account,
+            vault_authority,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+            token_program: *token_program,
+        }
+        .to_account_metas(None);
+
+        for row in chunk {
+            let beneficiary_ata = associated_token_address(&row.beneficiary, mint, token_program);
+            let (vesting_account, _) =
+                find_vesting_account(investor_account, &beneficiary_ata, grant_id, &solana_vesting::ID);
+            let (vault_account, _) = find_vault_account(&vesting_account, &solana_vesting::ID);
+
+            accounts.push(AccountMeta::new_readonly(beneficiary_ata, false));
+            accounts.push(AccountMeta::new(vesting_account, false));
+            accounts.push(AccountMeta::new(vault_account, false));
+            grant_id += 1;
+        }
+
+        let instruction = Instruction {
+            program_id: solana_vesting::ID,
+            accounts,
+            data: solana_vesting::instruction::InitializeBatch {
+                grants: chunk.iter().map(|row| row.grant.clone()).collect(),
+            }
+            .data(),
+        };
+
+        let signature = send(client, owner, &[instruction])?;
+        let lines: Vec<String> = chunk.iter().map(|row| row.line.to_string()).collect();
+        println!("lines {}: {}", lines.join(","), signature);
+    }
+
+    Ok(())
+}
+
+/// Creates the associated token accounts grants are paid out to, where missing
+fn create_missing_atas(
+    client: &RpcClient,
+    owner: &Keypair,
+    mint: &Pubkey,
+    token_program: &Pubkey,
+    rows: &[&GrantRow],
+) -> Result<(), Box<dyn Error>> {
+    let mut beneficiaries: Vec<Pubkey> = rows.iter().map(|row| row.beneficiary).collect();
+    beneficiaries.sort();
+    beneficiaries.dedup();
+
+    let atas: Vec<Pubkey> = beneficiaries
+        .iter()
+        .map(|beneficiary| associated_token_address(beneficiary, mint, token_program))
+        .collect();
+    let mut instructions = Vec::new();
+    for (chunk, beneficiaries) in atas
+        .chunks(ACCOUNTS_PER_REQUEST)
+        .zip(beneficiaries.chunks(ACCOUNTS_PER_REQUEST))
+    {
+        for (account, beneficiary) in client.get_multiple_accounts(chunk)?.iter().zip(beneficiaries) {
+            if account.is_none() {
+                instructions.push(create_associated_token_account(
+                    &owner.pubkey(),
+                    beneficiary,
+                    mint,
+                    token_program,
+                ));
+            }
+        }
+    }
+
+    for chunk in instructions.chunks(ATAS_PER_TRANSACTION) {
+        let signature = send(client, owner, chunk)?;
+        println!("created {} token accounts: {}", chunk.len(), signature);
+    }
+
+    Ok(())
+}
+
+/// The token program that owns `mint`
+fn fetch_token_program(client: &RpcClient, mint: &Pubkey) -> Result<Pubkey, Box<dyn Error>> {
+    let account = client.get_account(mint).map_err(|e| format!("{}: {}", mint, e))?;
+    if !is_token_program(&account.owner) {
+        return Err(format!("{} is not a token mint", mint).into());
+    }
+
+    Ok(account.owner)
+}
+
+/// The associated token account of `wallet` for `mint`. The address depends on the token
+/// program, which the `spl-associated-token-account` helpers fix to the SPL Token program.
+fn associated_token_address(wallet: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[wallet.as_ref(), token_program.as_ref(), mint.as_ref()],
+        &spl_associated_token_account::ID,
+    )
+    .0
+}
+
+fn create_associated_token_account(
+    payer: &Pubkey,
+    wallet: &Pubkey,
+    mint: &Pubkey,
+    token_program: &Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: spl_associated_token_account::ID,
+        accounts: vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(associated_token_address(wallet, mint, token_program), false),
+            AccountMeta::new_readonly(*wallet, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+            AccountMeta::new_readonly(*token_program, false),
+            AccountMeta::new_readonly(sysvar::rent::ID, false),
+        ],
+        data: vec![],
+    }
+}
+
+/// Every vesting account registered in the investor account
+fn fetch_grants(client: &RpcClient, investor_account: &Pubkey) -> Result<Vec<OnChainGrant>, Box<dyn Error>> {
+    let investor: InvestorAccount = fetch_account(client, investor_account)?;
+
+    let mut grants = Vec::new();
+    for chunk in investor.grants.chunks(ACCOUNTS_PER_REQUEST) {
+        for (address, account) in chunk.iter().zip(client.get_multiple_accounts(chunk)?) {
+            let account = account.ok_or(format!("vesting account {} not found", address))?;
+            grants.push(OnChainGrant {
+                address: *address,
+                account: VestingAccount::try_deserialize(&mut account.data.as_slice())?,
+            });
+        }
+    }
+
+    Ok(grants)
+}
+
+fn fetch_account<T: AccountDeserialize>(client: &RpcClient, address: &Pubkey) -> Result<T, Box<dyn Error>> {
+    let account = client
+        .get_account(address)
+        .map_err(|e| format!("{}: {}", address, e))?;
+    Ok(T::try_deserialize(&mut account.data.as_slice())?)
+}
+
+fn send(client: &RpcClient, payer: &Keypair, instructions: &[Instruction]) -> Result<Signature, Box<dyn Error>> {
+    let blockhash = client.get_latest_blockhash()?;
+    let transaction =
+        Transaction::new_signed_with_payer(instructions, Some(&payer.pubkey()), &[payer], blockhash);
+    Ok(client.send_and_confirm_transaction(&transaction)?)
+}
+
+fn print_report(findings: &[Finding]) {
+    for finding in findings {
+        println!("{}", finding);
+    }
+
+    let count = |f: fn(&Finding) -> bool| findings.iter().filter(|finding| f(finding)).count();
+    println!(
+        "{} matched, {} mismatched, {} missing, {} unexpected",
+        count(|finding| matches!(finding, Finding::Matched { .. })),
+        count(|finding| matches!(finding, Finding::Mismatched { .. })),
+        count(|finding| matches!(finding, Finding::Missing { .. })),
+        count(|finding| matches!(finding, Finding::Unexpected { .. })),
+    );
+}
@@ -0,0 +1,115 @@
+use std::fmt;
+
+use solana_sdk::pubkey::Pubkey;
+use solana_vesting::{ScheduleKind, VestingAccount};
+
+use crate::grants::GrantRow;
+
+/// A vesting account read from the chain
+pub struct OnChainGrant {
+    pub address: Pubkey,
+    pub account: VestingAccount,
+}
+
+/// How a line of the grants file compares to the chain
+pub enum Finding {
+    /// A grant with the same terms exists on chain
+    Matched { line: u64, address: Pubkey },
+    /// The beneficiary has a grant of that name, but with different terms
+    Mismatched { line: u64, address: Pubkey, fields: Vec<String> },
+    /// No grant of that name exists for the beneficiary
+    Missing { line: u64 },
+    /// A grant on chain that no line of the file accounts for
+    Unexpected { address: Pubkey },
+}
+
+/// Pairs every line of the grants file with a grant of the same beneficiary and name,
+/// preferring grants whose terms match exactly
+pub fn reconcile(rows: &[GrantRow], grants: &[OnChainGrant]) -> Vec<Finding> {
+    let mut matched = vec![false; grants.len()];
+    let mut findings = Vec::new();
+
+    for row in rows {
+        let candidates: Vec<usize> = (0..grants.len())
+            .filter(|&i| {
+                !matched[i]
+                    && grants[i].account.beneficiary == row.beneficiary
+                    && grants[i].account.name == row.grant.name
+            })
+            .collect();
+
+        let exact = candidates
+            .iter()
+            .copied()
+            .find(|&i| differences(row, &grants[i].account).is_empty());
+
+        match exact.or_else(|| candidates.first().copied()) {
+            Some(i) => {
+                matched[i] = true;
+                let fields = differences(row, &grants[i].account);
+                findings.push(if fields.is_empty() {
+                    Finding::Matched { line: row.line, address: grants[i].address }
+                } else {
+                    Finding::Mismatched { line: row.line, address: grants[i].address, fields }
+                });
+            }
+            None => findings.push(Finding::Missing { line: row.line }),
+        }
+    }
+
+    for (i, grant) in grants.iter().enumerate() {
+        if !matched[i] {
+            findings.push(Finding::Unexpected { address: grant.address });
+        }
+    }
+
+    findings
+}
+
+/// The terms of the grant that differ from the file, as `field: file != chain`
+fn differences(row: &GrantRow, account: &VestingAccount) -> Vec<String> {
+    let grant = &row.grant;
+    let mut fields = Vec::new();
+    let mut compare = |name: &str, expected: String, actual: String| {
+        if expected != actual {
+            fields.push(format!("{}: {} != {}", name, expected, actual));
+        }
+    };
+
+    compare(
+        "schedule",
+        format!("{:?}", ScheduleKind::Periodic),
+        format!("{:?}", account.schedule_kind),
+    );
+    compare("amount", grant.amount.to_string(), account.total_deposited_amount.to_string());
+    compare("start_ts", grant.start_ts.to_string(), account.start_ts.to_string());
+    compare("cliff_ts", grant.cliff_ts.to_string(), account.cliff_ts.to_string());
+    compare("duration", grant.duration.to_string(), account.duration.to_string());
+    compare("upfront_bps", grant.upfront_bps.to_string(), account.upfront_bps.to_string());
+    compare(
+        "withdraw_period",
+        grant.withdraw_period.to_string(),
+        account.withdraw_period.to_string(),
+    );
+    compare("revocable", grant.revocable.to_string(), account.revocable.to_string());
+
+    fields
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Finding::Matched { line, address } => write!(f, "line {}: ok {}", line, address),
+            Finding::Mismatched { line, address, fields } => {
+                write!(f, "line {}: MISMATCH {} ({})", line, address, fields.join(", "))
+            }
+            Finding::Missing { line } => write!(f, "line {}: MISSING on chain", line),
+            Finding::Unexpected { address } => write!(f, "UNEXPECTED on chain {}", address),
+        }
+    }
+}
+
+/// Whether the file and the chain agree on every grant
+pub fn is_reconciled(findings: &[Finding]) -> bool {
+    findings.iter().all(|finding| matches!(finding, Finding::Matched { .. }))
+}
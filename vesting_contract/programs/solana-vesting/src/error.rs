@@ -1,43 +1,97 @@
-use num_derive::FromPrimitive;
-use thiserror::Error;
-use solana_program::{decode_error::DecodeError, program_error::ProgramError};
+use anchor_lang::prelude::*;
 
-#[derive(Error, Debug, Copy, Clone, FromPrimitive)]
+/// Errors of the vesting program, returned as `6000 + discriminant`. The discriminants are
+/// part of the program's interface: never renumber a variant, only append new ones.
+#[error]
 pub enum LinearVestingError {
-    #[error("TokenVesting: no tokens are due")]
-    NoTokens,
-    #[error("Owner is wrong")]
-    NoMatchOwner,
-    #[error("Cannot revoke")]
-    NoRevoke,
-    #[error("Token already revoked")]
-    AlreadyRevoked,
-    #[error("Token already upfronted")]
-    AlreadyUpfronted,
-    #[error("Token not yet upfronted")]
-    NotYetUpfronted,
-    #[error("Wait for withdraw period")]
-    WaitForWithdrawPeriod,
-    #[error("Owner not matched")]
-    OwnerNotMatched,
-    #[error("Not approved")]
-    NotApproved,
-    #[error("Already disbled")]
-    AlreadyDisabled,
-    #[error("Already enabled")]
-    AlreadyEnabled,
-    #[error("AddTokenNotAllowed")]
-    AddTokenNotAllowed
+    #[msg("TokenVesting: no tokens are due")]
+    NoTokens = 0,
+    #[msg("Owner is wrong")]
+    NoMatchOwner = 1,
+    #[msg("Cannot revoke")]
+    NoRevoke = 2,
+    #[msg("Token already revoked")]
+    AlreadyRevoked = 3,
+    #[msg("Token already upfronted")]
+    AlreadyUpfronted = 4,
+    #[msg("Token not yet upfronted")]
+    NotYetUpfronted = 5,
+    #[msg("Wait for withdraw period")]
+    WaitForWithdrawPeriod = 6,
+    #[msg("Owner not matched")]
+    OwnerNotMatched = 7,
+    #[msg("Not approved")]
+    NotApproved = 8,
+    #[msg("Already disbled")]
+    AlreadyDisabled = 9,
+    #[msg("Already enabled")]
+    AlreadyEnabled = 10,
+    #[msg("AddTokenNotAllowed")]
+    AddTokenNotAllowed = 11,
+    #[msg("Vesting duration must be positive")]
+    InvalidDuration = 12,
+    #[msg("Cliff must be within the vesting period")]
+    InvalidCliff = 13,
+    #[msg("Cliff not reached yet")]
+    CliffNotReached = 14,
+    #[msg("Upfront basis points cannot exceed 10000")]
+    InvalidUpfrontPercent = 15,
+    #[msg("Withdraw period must be positive and within the vesting period")]
+    InvalidWithdrawPeriod = 16,
+    #[msg("Tranches must be ordered, non-empty and add up to the amount")]
+    InvalidTranches = 17,
+    #[msg("Schedule has no upfront release")]
+    NoUpfrontInSchedule = 18,
+    #[msg("Beneficiary transfer needs the owner's approval")]
+    TransferNotApproved = 19,
+    #[msg("New beneficiary is the current beneficiary")]
+    SameBeneficiary = 20,
+    #[msg("Grant cannot move to that status")]
+    InvalidStatusTransition = 21,
+    #[msg("Only completed or revoked grants can be closed")]
+    GrantNotFinished = 22,
+    #[msg("Vault still holds tokens")]
+    VaultNotEmpty = 23,
+    #[msg("Batch grants do not match the accounts provided")]
+    InvalidBatch = 24,
+    #[msg("Signer is neither the beneficiary nor the owner of the grant")]
+    Unauthorized = 25,
+    #[msg("Only unvested tokens in the vault can be staked")]
+    StakeExceedsUnvested = 26,
+    #[msg("Beneficiary can only unstake vested tokens")]
+    UnstakeExceedsVested = 27,
+    #[msg("Not that much of the grant is staked")]
+    InsufficientStake = 28,
+    #[msg("Grant is staked in another pool")]
+    StakedInOtherPool = 29,
+    #[msg("Grant has tokens staked")]
+    GrantStaked = 30,
+    #[msg("Arithmetic overflow")]
+    MathOverflow = 31,
+    #[msg("Token account has the wrong mint")]
+    InvalidMint = 32,
+    #[msg("Vault is not the grant's vault")]
+    InvalidVault = 33,
+    #[msg("Nothing is left of the schedule to spread the top-up over")]
+    NoRemainingSchedule = 34,
+    #[msg("Grant has no room for another top-up")]
+    TooManyTopUps = 35,
+    #[msg("Top-up cannot start before the grant")]
+    InvalidTopUp = 36,
+    #[msg("Not the token program of the mint")]
+    InvalidTokenProgram = 37,
+    #[msg("Only active or paused grants can be transferred")]
+    GrantNotTransferable = 38,
+    #[msg("Token account is not the grant's payout account")]
+    InvalidBeneficiaryAta = 39,
+    #[msg("Grant name is too long")]
+    NameTooLong = 40,
+    #[msg("Investor account has no room for another grant")]
+    TooManyGrants = 41,
+    #[msg("Mint has an extension the vault cannot account for")]
+    UnsupportedMintExtension = 42,
+    #[msg("Grant has no room for another tranche")]
+    TooManyTranches = 43,
+    #[msg("Tranche must unlock in the future")]
+    TrancheInPast = 44,
 }
-
-impl From<LinearVestingError> for ProgramError {
-    fn from(e: LinearVestingError) -> Self {
-        ProgramError::Custom(e as u32)
-    }
-}
-
-impl<T> DecodeError<T> for LinearVestingError {
-    fn type_of() -> &'static str {
-        "Error"
-    }
-}
\ No newline at end of file
@@ -1,8 +1,11 @@
 use anchor_lang::{prelude::*};
-use anchor_spl::token::{self, Mint, SetAuthority, TokenAccount, Transfer};
-use spl_token::instruction::AuthorityType;
+use anchor_lang::solana_program::{program::{invoke, invoke_signed}, system_instruction, system_program};
+use anchor_spl::token::{CloseAccount, InitializeAccount};
 pub mod error;
+pub mod staking;
+pub mod token_interface;
 use crate::{error::LinearVestingError};
+use crate::token_interface::{token_account, TransferChecked};
 
 declare_id!("A61XuzXmCHwTcaHEiQ1wJgpp3crMMdoxRY3hXau7LnRP");
 
@@ -10,11 +13,188 @@ const VAULT_PDA_SEED: &[u8] = b"token-vault";
 const VAULT_AUTHORITY_PDA_SEED: &[u8] = b"vault-authority";
 const INVESTOR_ACCOUNT_PDA_SEED: &[u8] = b"investor-account";
 
-const UPFRONT_PERCENT: u64 = 1000;
-const WITHDRAW_PERCENT: u64 = 375;
-const WITHDRAW_PERIOD: i64 = 1;
-// const WITHDRAW_PERIOD: i64 = 1 * 30 * 86400;
+const BPS_DENOMINATOR: u64 = 10000;
+const MAX_TRANCHES: usize = 8;
+const MAX_TOP_UPS: usize = 8;
+/// Longest grant name, in bytes
+pub const MAX_NAME_LEN: usize = 32;
+/// Grants an investor account tracks at once; closing a grant frees its slot
+pub const MAX_PROJECT_GRANTS: usize = 150;
+/// `VestingAccount` with its name, tranches and top-ups at their maximum lengths
+const VESTING_ACCOUNT_SPACE: usize = 8 // discriminator
+    + 32 * 6 // beneficiary, beneficiary_ata, investor_account, owner, mint, staking_pool
+    + 8 * 12 // grant_id, start_ts, withdraw_ts, cliff_ts, duration, upfront_bps, withdraw_period,
+             // total_deposited_amount, released_amount, revoked_ts, paused_ts, staked_amount
+    + 4 // claimed_count
+    + 6 // revocable, transfer_requires_approval, status, shift_on_resume, upfronted, schedule_kind
+    + (4 + MAX_NAME_LEN) // name
+    + (4 + MAX_TRANCHES * (8 + 8)) // tranches
+    + (4 + MAX_TOP_UPS * (8 + 8 + 8)); // top_ups
+/// `InvestorAccount` with `MAX_PROJECT_GRANTS` grants
+const INVESTOR_ACCOUNT_SPACE: usize = 8 // discriminator
+    + (4 + MAX_PROJECT_GRANTS * 32) // investors
+    + 32 * 2 // owner, mint
+    + 8 // grant_count
+    + (4 + MAX_PROJECT_GRANTS * 32); // grants
+/// Grants per `initialize_batch`, bounded by the accounts that fit in one transaction
+pub const MAX_BATCH_GRANTS: usize = 4;
+/// Remaining accounts per batched grant: beneficiary ATA, vesting account, vault
+pub const BATCH_ACCOUNTS_PER_GRANT: usize = 3;
+
+
+/// The investor account of the project granting `mint` on behalf of `owner`
+pub fn find_investor_account(owner: &Pubkey, mint: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[INVESTOR_ACCOUNT_PDA_SEED, owner.as_ref(), mint.as_ref()],
+        program_id,
+    )
+}
+
+/// The vesting account of grant `grant_id` of an investor account, paid out to `beneficiary_ata`
+pub fn find_vesting_account(
+    investor_account: &Pubkey,
+    beneficiary_ata: &Pubkey,
+    grant_id: u64,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[investor_account.as_ref(), beneficiary_ata.as_ref(), grant_id.to_le_bytes().as_ref()],
+        program_id,
+    )
+}
+
+/// The token account holding the unreleased tokens of a grant
+pub fn find_vault_account(vesting_account: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[VAULT_PDA_SEED, vesting_account.as_ref()], program_id)
+}
+
+/// The PDA that owns the vaults of every grant in an investor account
+pub fn find_vault_authority(investor_account: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[VAULT_AUTHORITY_PDA_SEED, investor_account.as_ref()], program_id)
+}
+
+/// Creates a rent-exempt account at a PDA of this program. Like Anchor's `init`, an address
+/// someone already sent lamports to is topped up, allocated and assigned instead, as
+/// `create_account` refuses accounts with a balance.
+fn create_pda_account<'info>(
+    payer: &AccountInfo<'info>,
+    account: &AccountInfo<'info>,
+    space: usize,
+    account_owner: &Pubkey,
+    system_program: &AccountInfo<'info>,
+    rent: &Rent,
+    seeds: &[&[u8]],
+) -> ProgramResult {
+    let current_lamports = account.lamports();
+    if current_lamports == 0 {
+        return invoke_signed(
+            &system_instruction::create_account(
+                payer.key,
+                account.key,
+                rent.minimum_balance(space),
+                space as u64,
+                account_owner,
+            ),
+            &[payer.clone(), account.clone(), system_program.clone()],
+            &[seeds],
+        );
+    }
+
+    let required_lamports = rent
+        .minimum_balance(space)
+        .max(1)
+        .saturating_sub(current_lamports);
+    if required_lamports > 0 {
+        invoke(
+            &system_instruction::transfer(payer.key, account.key, required_lamports),
+            &[payer.clone(), account.clone(), system_program.clone()],
+        )?;
+    }
+    invoke_signed(
+        &system_instruction::allocate(account.key, space as u64),
+        &[account.clone(), system_program.clone()],
+        &[seeds],
+    )?;
+    invoke_signed(
+        &system_instruction::assign(account.key, account_owner),
+        &[account.clone(), system_program.clone()],
+        &[seeds],
+    )
+}
+
+/// Creates the vault of a grant at its PDA under the token program of `mint`, held by the
+/// vault authority. `space` comes from `token_interface::account_space`.
+#[allow(clippy::too_many_arguments)]
+fn create_vault<'info>(
+    payer: &AccountInfo<'info>,
+    vault: &AccountInfo<'info>,
+    space: usize,
+    vesting_account: &Pubkey,
+    vault_bump: u8,
+    mint: &AccountInfo<'info>,
+    vault_authority: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    rent: &Sysvar<'info, Rent>,
+    token_program: &AccountInfo<'info>,
+) -> ProgramResult {
+    create_pda_account(
+        payer,
+        vault,
+        space,
+        token_program.key,
+        system_program,
+        rent,
+        &[VAULT_PDA_SEED, vesting_account.as_ref(), &[vault_bump]],
+    )?;
+    token_interface::initialize_account(CpiContext::new(
+        token_program.clone(),
+        InitializeAccount {
+            account: vault.clone(),
+            mint: mint.clone(),
+            authority: vault_authority.clone(),
+            rent: rent.to_account_info(),
+        },
+    ))
+}
+
+/// Checks that a grant name fits the space reserved for it
+fn validate_name(name: &str) -> ProgramResult {
+    if name.len() > MAX_NAME_LEN {
+        return Err(LinearVestingError::NameTooLong.into());
+    }
+
+    Ok(())
+}
+
+/// Checks the terms of an upfront + periodic schedule
+fn validate_periodic_schedule(
+    start_ts: i64,
+    cliff_ts: i64,
+    duration: i64,
+    upfront_bps: u64,
+    withdraw_period: i64,
+) -> ProgramResult {
+    if duration <= 0 {
+        return Err(LinearVestingError::InvalidDuration.into());
+    }
+
+    let end_ts = start_ts
+        .checked_add(duration)
+        .ok_or(LinearVestingError::MathOverflow)?;
+    if cliff_ts < start_ts || cliff_ts > end_ts {
+        return Err(LinearVestingError::InvalidCliff.into());
+    }
+
+    if upfront_bps > BPS_DENOMINATOR {
+        return Err(LinearVestingError::InvalidUpfrontPercent.into());
+    }
+
+    if withdraw_period <= 0 || withdraw_period > duration {
+        return Err(LinearVestingError::InvalidWithdrawPeriod.into());
+    }
 
+    Ok(())
+}
 
 #[program]
 pub mod solana_vesting {
@@ -24,14 +204,20 @@ pub mod solana_vesting {
         ctx: Context<InitializeVesting>,
     ) -> ProgramResult {
         ctx.accounts.investor_account.owner = *ctx.accounts.owner.key;
+        ctx.accounts.investor_account.mint = *ctx.accounts.mint.key;
         Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn initialize(
         ctx: Context<Initialize>,
         amount: u64,
         name: String,
         start_ts: i64,
+        cliff_ts: i64,
+        duration: i64,
+        upfront_bps: u64,
+        withdraw_period: i64,
         revocable: bool,
     ) -> ProgramResult {
 
@@ -39,101 +225,187 @@ pub mod solana_vesting {
             return Err(LinearVestingError::OwnerNotMatched.into());
         }
 
-        ctx.accounts.vesting_account.start_ts = start_ts;
-        ctx.accounts.vesting_account.withdraw_ts = start_ts;
-        ctx.accounts.vesting_account.revocable = revocable;
-        ctx.accounts.vesting_account.name = name;
+        validate_periodic_schedule(start_ts, cliff_ts, duration, upfront_bps, withdraw_period)?;
 
-        ctx.accounts.vesting_account.beneficiary = *ctx.accounts.beneficiary.key;
-        ctx.accounts.vesting_account.owner = *ctx.accounts.owner.key;
-        ctx.accounts.vesting_account.mint = *ctx.accounts.mint.to_account_info().key;
+        ctx.accounts.vesting_account.set_periodic_schedule(
+            start_ts,
+            cliff_ts,
+            duration,
+            upfront_bps,
+            withdraw_period,
+        );
 
-        ctx.accounts.vesting_account.total_deposited_amount = amount;
-        ctx.accounts.vesting_account.released_amount = 0;
-        ctx.accounts.vesting_account.claimed_count = 0;
-        ctx.accounts.vesting_account.revoked = false;
-        ctx.accounts.vesting_account.upfronted = false;
-        ctx.accounts.vesting_account.approved = true;
+        ctx.accounts.register_grant(ctx.program_id, amount, name, revocable)
+    }
 
-        // register an investor to list
-        let investor_account = &mut ctx.accounts.investor_account;
-        investor_account.investors.push(*ctx.accounts.beneficiary.key);
+    /// Creates up to `MAX_BATCH_GRANTS` periodic grants at once. For each grant the remaining
+    /// accounts hold the beneficiary's token account, the vesting account and the vault, in
+    /// that order; the owner of the token account is the beneficiary
+    pub fn initialize_batch<'info>(
+        ctx: Context<'_, '_, '_, 'info, InitializeBatch<'info>>,
+        grants: Vec<BatchGrant>,
+    ) -> ProgramResult {
+        if grants.is_empty() || grants.len() > MAX_BATCH_GRANTS {
+            return Err(LinearVestingError::InvalidBatch.into());
+        }
 
+        if ctx.remaining_accounts.len() != grants.len() * BATCH_ACCOUNTS_PER_GRANT {
+            return Err(LinearVestingError::InvalidBatch.into());
+        }
 
-        let (vault_authority, _vault_authority_bump) =
-            Pubkey::find_program_address(&[VAULT_AUTHORITY_PDA_SEED], ctx.program_id);
-        
-        token::set_authority(
-            ctx.accounts.into_set_authority_context(),
-            AuthorityType::AccountOwner,
-            Some(vault_authority),
-        )?;
+        let total_amount = grants
+            .iter()
+            .try_fold(0u64, |total, grant| total.checked_add(grant.amount))
+            .ok_or(LinearVestingError::InvalidBatch)?;
+        if token_account(&ctx.accounts.owner_token_account)?.amount < total_amount {
+            return Err(LinearVestingError::InvalidBatch.into());
+        }
 
-        token::transfer(
-            ctx.accounts.into_transfer_to_pda_context(),
-            ctx.accounts.vesting_account.total_deposited_amount,
-        )?;
+        // every vault of the batch has the same size
+        let vault_space =
+            token_interface::account_space(&ctx.accounts.mint, &ctx.accounts.token_program)?;
+        for (grant, accounts) in grants
+            .into_iter()
+            .zip(ctx.remaining_accounts.chunks(BATCH_ACCOUNTS_PER_GRANT))
+        {
+            ctx.accounts.create_grant(ctx.program_id, grant, accounts, vault_space)?;
+        }
 
         Ok(())
     }
 
+    /// Creates a grant that unlocks in tranches at fixed dates instead of upfront + periodic releases
+    pub fn initialize_milestone(
+        ctx: Context<Initialize>,
+        amount: u64,
+        name: String,
+        tranches: Vec<Tranche>,
+        revocable: bool,
+    ) -> ProgramResult {
+
+        if ctx.accounts.owner.key != &ctx.accounts.investor_account.owner {
+            return Err(LinearVestingError::OwnerNotMatched.into());
+        }
+
+        if tranches.is_empty() || tranches.len() > MAX_TRANCHES {
+            return Err(LinearVestingError::InvalidTranches.into());
+        }
+
+        let mut tranche_total: u64 = 0;
+        for (i, tranche) in tranches.iter().enumerate() {
+            if tranche.amount == 0 || (i > 0 && tranche.unlock_ts <= tranches[i - 1].unlock_ts) {
+                return Err(LinearVestingError::InvalidTranches.into());
+            }
+            tranche_total = tranche_total
+                .checked_add(tranche.amount)
+                .ok_or(LinearVestingError::InvalidTranches)?;
+        }
+
+        if tranche_total != amount {
+            return Err(LinearVestingError::InvalidTranches.into());
+        }
+
+        let first_unlock_ts = tranches[0].unlock_ts;
+        let last_unlock_ts = tranches[tranches.len() - 1].unlock_ts;
+
+        ctx.accounts.vesting_account.schedule_kind = ScheduleKind::Milestone;
+        ctx.accounts.vesting_account.start_ts = first_unlock_ts;
+        ctx.accounts.vesting_account.withdraw_ts = first_unlock_ts;
+        ctx.accounts.vesting_account.cliff_ts = first_unlock_ts;
+        ctx.accounts.vesting_account.duration = last_unlock_ts - first_unlock_ts;
+        ctx.accounts.vesting_account.tranches = tranches;
+        // there is no upfront release in a milestone schedule
+        ctx.accounts.vesting_account.upfronted = true;
+
+        ctx.accounts.register_grant(ctx.program_id, amount, name, revocable)
+    }
+
+    /// Tops the grant up with `amount` at any point of its schedule, added as `policy` says.
+    /// Only what reaches the vault after transfer fees is added. A completed grant becomes
+    /// active again.
     pub fn add_token_to_vesting(
         ctx: Context<AddTokenToVestingAccount>,
         amount: u64,
+        policy: TopUpPolicy,
     ) -> ProgramResult {
+        if amount == 0 {
+            return Err(LinearVestingError::NoTokens.into());
+        }
 
-        if ctx.accounts.vesting_account.claimed_count > 0 {
+        if ctx.accounts.vesting_account.status == VestingStatus::Revoked {
             return Err(LinearVestingError::AddTokenNotAllowed.into());
         }
-        
-        ctx.accounts.vesting_account.total_deposited_amount += amount;
-        
-        token::transfer(
+
+        let vault_before = token_account(&ctx.accounts.vault_account)?.amount;
+        let decimals = token_interface::mint(&ctx.accounts.mint)?.decimals;
+        token_interface::transfer_checked(
             ctx.accounts.into_transfer_to_pda_context(),
             amount,
+            decimals,
         )?;
+        let received = token_account(&ctx.accounts.vault_account)?
+            .amount
+            .checked_sub(vault_before)
+            .ok_or(LinearVestingError::MathOverflow)?;
+        if received == 0 {
+            return Err(LinearVestingError::NoTokens.into());
+        }
+
+        let vesting_key = ctx.accounts.vesting_account.key();
+        let now = Clock::get()?.unix_timestamp;
+        ctx.accounts.vesting_account.add_top_up(received, policy, now)?;
+        ctx.accounts.vesting_account.record_deposit(vesting_key, received, now)?;
+        if ctx.accounts.vesting_account.status == VestingStatus::Completed {
+            ctx.accounts.vesting_account.set_status(vesting_key, VestingStatus::Active, now)?;
+        }
 
         Ok(())
     }
 
     pub fn upfront(
         ctx: Context<Upfront>,
-        current_ts: i64
     ) -> ProgramResult {
+        if ctx.accounts.vesting_account.schedule_kind != ScheduleKind::Periodic {
+            return Err(LinearVestingError::NoUpfrontInSchedule.into());
+        }
+
         if ctx.accounts.vesting_account.upfronted {
             return Err(LinearVestingError::AlreadyUpfronted.into());
         }
 
-        if !ctx.accounts.vesting_account.approved {
+        if ctx.accounts.vesting_account.status != VestingStatus::Active {
             return Err(LinearVestingError::NotApproved.into());
         }
 
-        let current_balance = ctx.accounts.vault_account.amount;
-        let total_balance = current_balance + ctx.accounts.vesting_account.released_amount;
-        let unreleased_token = total_balance * UPFRONT_PERCENT / 100 / 100;
-
-        ctx.accounts.vesting_account.released_amount = ctx
-            .accounts
-            .vesting_account.released_amount
-            .checked_add(unreleased_token)
-            .unwrap();
-
-        ctx.accounts.vesting_account.claimed_count = ctx
-            .accounts
-            .vesting_account.claimed_count
-            .checked_add(1)
-            .unwrap();
+        let current_ts = Clock::get()?.unix_timestamp;
+        let unreleased_token = ctx.accounts.vesting_account.upfront_amount();
+        let vesting_key = ctx.accounts.vesting_account.key();
+        ctx.accounts.vesting_account.record_release(
+            vesting_key,
+            ReleaseKind::Upfront,
+            unreleased_token,
+            current_ts,
+        )?;
 
+        // `withdraw_ts` stays on the period grid, so periods completed before a late upfront
+        // can be withdrawn right away
         ctx.accounts.vesting_account.upfronted = true;
-        ctx.accounts.vesting_account.withdraw_ts = current_ts;
+        ctx.accounts.vesting_account.complete_if_released(vesting_key, current_ts)?;
 
+        let investor_account = ctx.accounts.vesting_account.investor_account;
         let (_vault_authority, vault_authority_bump) =
-            Pubkey::find_program_address(&[VAULT_AUTHORITY_PDA_SEED], ctx.program_id);
-        let authority_seeds = &[&VAULT_AUTHORITY_PDA_SEED[..], &[vault_authority_bump]];
-        
-        token::transfer(
-            ctx.accounts.into_transfer_to_owner_context().with_signer(&[&authority_seeds[..]]),
+            find_vault_authority(&investor_account, ctx.program_id);
+        let authority_seeds = &[
+            &VAULT_AUTHORITY_PDA_SEED[..],
+            investor_account.as_ref(),
+            &[vault_authority_bump],
+        ];
+
+        let decimals = token_interface::mint(&ctx.accounts.mint)?.decimals;
+        token_interface::transfer_checked(
+            ctx.accounts.into_transfer_to_beneficiary_context().with_signer(&[&authority_seeds[..]]),
             unreleased_token,
+            decimals,
         )?;
 
         Ok(())
@@ -141,56 +413,70 @@ pub mod solana_vesting {
 
     pub fn withdraw(
         ctx: Context<Withdraw>,
-        withdraw_ts: i64,
     ) -> ProgramResult {
 
         if !ctx.accounts.vesting_account.upfronted {
             return Err(LinearVestingError::NotYetUpfronted.into());
         }
 
-        if !ctx.accounts.vesting_account.approved {
+        if ctx.accounts.vesting_account.status != VestingStatus::Active {
             return Err(LinearVestingError::NotApproved.into());
         }
 
-        let current_time = withdraw_ts;
-        if ctx.accounts.vesting_account.withdraw_ts + WITHDRAW_PERIOD > current_time {
-            return Err(LinearVestingError::WaitForWithdrawPeriod.into());
+        let current_time = Clock::get()?.unix_timestamp;
+        let mut release_ts = current_time;
+        if ctx.accounts.vesting_account.schedule_kind == ScheduleKind::Periodic {
+            if current_time < ctx.accounts.vesting_account.cliff_ts {
+                return Err(LinearVestingError::CliffNotReached.into());
+            }
+
+            // pay every period completed since the last release, not just one
+            release_ts = ctx.accounts.vesting_account.last_period_end_ts(current_time)?;
+            if release_ts <= ctx.accounts.vesting_account.withdraw_ts {
+                return Err(LinearVestingError::WaitForWithdrawPeriod.into());
+            }
         }
 
-        let current_balance = ctx.accounts.vault_account.amount;
-        let total_balance = current_balance + ctx.accounts.vesting_account.released_amount;
-        if current_balance <= 0 {
+        let unreleased_token = ctx
+            .accounts
+            .vesting_account
+            .vested_amount(current_time)?
+            .saturating_sub(ctx.accounts.vesting_account.released_amount);
+        if unreleased_token == 0 {
             return Err(LinearVestingError::NoTokens.into());
         }
-        
-        let unreleased_token = total_balance * WITHDRAW_PERCENT / 10000;
 
-        ctx.accounts.vesting_account.released_amount = ctx
-            .accounts
-            .vesting_account.released_amount
-            .checked_add(unreleased_token)
-            .unwrap();
-        
-        ctx.accounts.vesting_account.claimed_count = ctx
-            .accounts
-            .vesting_account.claimed_count
-            .checked_add(1)
-            .unwrap();
+        let vesting_key = ctx.accounts.vesting_account.key();
+        ctx.accounts.vesting_account.record_release(
+            vesting_key,
+            ReleaseKind::Withdraw,
+            unreleased_token,
+            current_time,
+        )?;
 
-        ctx.accounts.vesting_account.withdraw_ts = current_time;
+        ctx.accounts.vesting_account.withdraw_ts = release_ts;
+        ctx.accounts.vesting_account.complete_if_released(vesting_key, current_time)?;
 
+        let investor_account = ctx.accounts.vesting_account.investor_account;
         let (_vault_authority, vault_authority_bump) =
-            Pubkey::find_program_address(&[VAULT_AUTHORITY_PDA_SEED], ctx.program_id);
-        let authority_seeds = &[&VAULT_AUTHORITY_PDA_SEED[..], &[vault_authority_bump]];
-        
-        token::transfer(
-            ctx.accounts.into_transfer_to_owner_context().with_signer(&[&authority_seeds[..]]),
+            find_vault_authority(&investor_account, ctx.program_id);
+        let authority_seeds = &[
+            &VAULT_AUTHORITY_PDA_SEED[..],
+            investor_account.as_ref(),
+            &[vault_authority_bump],
+        ];
+
+        let decimals = token_interface::mint(&ctx.accounts.mint)?.decimals;
+        token_interface::transfer_checked(
+            ctx.accounts.into_transfer_to_beneficiary_context().with_signer(&[&authority_seeds[..]]),
             unreleased_token,
+            decimals,
         )?;
 
         Ok(())
     }
 
+    /// Resumes a paused grant, pushing its schedule back by the paused time if requested on pause
     pub fn enable_account(
         ctx: Context<EnableAccount>,
     ) -> ProgramResult {
@@ -198,28 +484,41 @@ pub mod solana_vesting {
             return Err(LinearVestingError::OwnerNotMatched.into());
         }
 
-        if ctx.accounts.vesting_account.approved {
+        if ctx.accounts.vesting_account.status == VestingStatus::Active {
             return Err(LinearVestingError::AlreadyEnabled.into());
         }
 
-        ctx.accounts.vesting_account.approved = true;
-        ctx.accounts.vesting_account.revoked = false;
-        Ok(())
+        // completed grants only become active again through a top-up
+        if ctx.accounts.vesting_account.status != VestingStatus::Paused {
+            return Err(LinearVestingError::InvalidStatusTransition.into());
+        }
+
+        let now = Clock::get()?.unix_timestamp;
+        ctx.accounts.vesting_account.settle_pause(now)?;
+
+        let vesting_key = ctx.accounts.vesting_account.key();
+        ctx.accounts.vesting_account.set_status(vesting_key, VestingStatus::Active, now)
     }
 
+    /// Pauses a grant; with `shift_schedule` the paused time does not count towards vesting
     pub fn disable_account(
         ctx: Context<DisableAccount>,
+        shift_schedule: bool,
     ) -> ProgramResult {
         if ctx.accounts.owner.key != &ctx.accounts.investor_account.owner {
             return Err(LinearVestingError::OwnerNotMatched.into());
         }
 
-        if !ctx.accounts.vesting_account.approved {
+        if ctx.accounts.vesting_account.status == VestingStatus::Paused {
             return Err(LinearVestingError::AlreadyDisabled.into());
         }
 
-        ctx.accounts.vesting_account.approved = false;
-        Ok(())
+        let now = Clock::get()?.unix_timestamp;
+        ctx.accounts.vesting_account.paused_ts = now;
+        ctx.accounts.vesting_account.shift_on_resume = shift_schedule;
+
+        let vesting_key = ctx.accounts.vesting_account.key();
+        ctx.accounts.vesting_account.set_status(vesting_key, VestingStatus::Paused, now)
     }
 
     pub fn rename_account(
@@ -230,14 +529,63 @@ pub mod solana_vesting {
             return Err(LinearVestingError::OwnerNotMatched.into());
         }
 
-        if !ctx.accounts.vesting_account.approved {
+        if ctx.accounts.vesting_account.status != VestingStatus::Active {
             return Err(LinearVestingError::AlreadyDisabled.into());
         }
 
+        validate_name(&name)?;
         ctx.accounts.vesting_account.name = name;
         Ok(())
     }
 
+    /// Sets whether moving the grant to a new beneficiary needs the owner to co-sign
+    pub fn set_transfer_approval(
+        ctx: Context<SetTransferApproval>,
+        required: bool,
+    ) -> ProgramResult {
+        if ctx.accounts.owner.key != &ctx.accounts.investor_account.owner {
+            return Err(LinearVestingError::OwnerNotMatched.into());
+        }
+
+        ctx.accounts.vesting_account.transfer_requires_approval = required;
+        Ok(())
+    }
+
+    /// Moves the grant to a new beneficiary, keeping its schedule and released amount
+    pub fn transfer_beneficiary(
+        ctx: Context<TransferBeneficiary>,
+    ) -> ProgramResult {
+        if ctx.accounts.vesting_account.transfer_requires_approval && !ctx.accounts.owner.is_signer {
+            return Err(LinearVestingError::TransferNotApproved.into());
+        }
+
+        let status = ctx.accounts.vesting_account.status;
+        if status != VestingStatus::Active && status != VestingStatus::Paused {
+            return Err(LinearVestingError::GrantNotTransferable.into());
+        }
+
+        // the locked stake belongs to the current beneficiary's staking account
+        if ctx.accounts.vesting_account.staked_amount > 0 {
+            return Err(LinearVestingError::GrantStaked.into());
+        }
+
+        let new_beneficiary = *ctx.accounts.new_beneficiary.key;
+        if new_beneficiary == ctx.accounts.vesting_account.beneficiary {
+            return Err(LinearVestingError::SameBeneficiary.into());
+        }
+
+        ctx.accounts.vesting_account.beneficiary = new_beneficiary;
+        ctx.accounts.vesting_account.beneficiary_ata = ctx.accounts.new_beneficiary_ata.key();
+
+        let vesting_key = ctx.accounts.vesting_account.key();
+        let investor_account = &mut ctx.accounts.investor_account;
+        if let Some(i) = investor_account.grants.iter().position(|grant| *grant == vesting_key) {
+            investor_account.investors[i] = new_beneficiary;
+        }
+
+        Ok(())
+    }
+
     pub fn revoke(
         ctx: Context<Revoke>
     ) -> ProgramResult {
@@ -247,29 +595,236 @@ pub mod solana_vesting {
         if !ctx.accounts.vesting_account.revocable {
             return Err(LinearVestingError::NoRevoke.into());
         }
-        if ctx.accounts.vesting_account.revoked {
+        if ctx.accounts.vesting_account.status == VestingStatus::Revoked {
             return Err(LinearVestingError::AlreadyRevoked.into());
         }
+        if ctx.accounts.vesting_account.staked_amount > 0 {
+            return Err(LinearVestingError::GrantStaked.into());
+        }
+
+        // whatever vested up to now is still owed to the beneficiary
+        let revoked_ts = Clock::get()?.unix_timestamp;
+        if ctx.accounts.vesting_account.status == VestingStatus::Paused {
+            ctx.accounts.vesting_account.settle_pause(revoked_ts)?;
+        }
+        let vault_amount = token_account(&ctx.accounts.vault_account)?.amount;
+        let vested_unreleased = ctx
+            .accounts
+            .vesting_account
+            .vested_amount(revoked_ts)?
+            .saturating_sub(ctx.accounts.vesting_account.released_amount)
+            .min(vault_amount);
+        let refund = vault_amount - vested_unreleased;
+
+        let vesting_key = ctx.accounts.vesting_account.key();
+        if vested_unreleased > 0 {
+            ctx.accounts.vesting_account.record_release(
+                vesting_key,
+                ReleaseKind::Revoke,
+                vested_unreleased,
+                revoked_ts,
+            )?;
+        }
+        // freeze the schedule: nothing vests after the revocation
+        ctx.accounts.vesting_account.total_deposited_amount =
+            ctx.accounts.vesting_account.released_amount;
+        ctx.accounts.vesting_account.revoked_ts = revoked_ts;
+        ctx.accounts.vesting_account.upfronted = true;
+
+        if refund > 0 {
+            emit!(TokensRefunded {
+                vesting_account: vesting_key,
+                owner: ctx.accounts.vesting_account.owner,
+                amount: refund,
+                timestamp: revoked_ts,
+            });
+        }
+        ctx.accounts.vesting_account.set_status(vesting_key, VestingStatus::Revoked, revoked_ts)?;
+
+        let investor_account = ctx.accounts.vesting_account.investor_account;
+        let (_vault_authority, vault_authority_bump) =
+            find_vault_authority(&investor_account, ctx.program_id);
+        let authority_seeds = &[
+            &VAULT_AUTHORITY_PDA_SEED[..],
+            investor_account.as_ref(),
+            &[vault_authority_bump],
+        ];
+
+        let decimals = token_interface::mint(&ctx.accounts.mint)?.decimals;
+        if vested_unreleased > 0 {
+            token_interface::transfer_checked(
+                ctx.accounts.into_transfer_to_beneficiary_context().with_signer(&[&authority_seeds[..]]),
+                vested_unreleased,
+                decimals,
+            )?;
+        }
+
+        if refund > 0 {
+            token_interface::transfer_checked(
+                ctx.accounts.into_transfer_to_owner_context().with_signer(&[&authority_seeds[..]]),
+                refund,
+                decimals,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Stakes unvested tokens of the grant in a staking pool for the beneficiary. The position
+    /// earns rewards for the beneficiary while the tokens stay locked to the grant. A transfer
+    /// fee taken on the way is lost to the grant.
+    pub fn stake_vesting(
+        ctx: Context<StakeVesting>,
+        amount: u64,
+    ) -> ProgramResult {
+        if ctx.accounts.authority.key() != ctx.accounts.vesting_account.beneficiary {
+            return Err(LinearVestingError::Unauthorized.into());
+        }
+
+        if ctx.accounts.vesting_account.status != VestingStatus::Active {
+            return Err(LinearVestingError::NotApproved.into());
+        }
+
+        let staking_pool = ctx.accounts.staking_pool.key();
+        if ctx.accounts.vesting_account.staked_amount > 0
+            && ctx.accounts.vesting_account.staking_pool != staking_pool
+        {
+            return Err(LinearVestingError::StakedInOtherPool.into());
+        }
+
+        // vested tokens stay in the vault so they can be withdrawn
+        let now = Clock::get()?.unix_timestamp;
+        let vested_unreleased = ctx
+            .accounts
+            .vesting_account
+            .vested_amount(now)?
+            .saturating_sub(ctx.accounts.vesting_account.released_amount);
+        let unvested_in_vault = token_account(&ctx.accounts.vault_account)?
+            .amount
+            .saturating_sub(vested_unreleased);
+        if amount > unvested_in_vault {
+            return Err(LinearVestingError::StakeExceedsUnvested.into());
+        }
+
+        let investor_account = ctx.accounts.vesting_account.investor_account;
+        let (_vault_authority, vault_authority_bump) =
+            find_vault_authority(&investor_account, ctx.program_id);
+        let authority_seeds = &[
+            &VAULT_AUTHORITY_PDA_SEED[..],
+            investor_account.as_ref(),
+            &[vault_authority_bump],
+        ];
+
+        let staked_before = token_account(&ctx.accounts.staking_vault)?.amount;
+        staking::stake_locked(&ctx.accounts.locked_stake(), amount, &[&authority_seeds[..]])?;
+        let staked = token_account(&ctx.accounts.staking_vault)?
+            .amount
+            .checked_sub(staked_before)
+            .ok_or(LinearVestingError::MathOverflow)?;
+
+        ctx.accounts.vesting_account.staking_pool = staking_pool;
+        ctx.accounts.vesting_account.staked_amount = ctx
+            .accounts
+            .vesting_account
+            .staked_amount
+            .checked_add(staked)
+            .ok_or(LinearVestingError::MathOverflow)?;
+        ctx.accounts.vesting_account.withhold_fee(amount.saturating_sub(staked))
+    }
+
+    /// Moves staked tokens of the grant back to its vault. The owner can unstake everything,
+    /// the beneficiary only what has vested and not been released yet. A transfer fee taken
+    /// on the way is lost to the grant.
+    pub fn unstake_vesting(
+        ctx: Context<StakeVesting>,
+        amount: u64,
+    ) -> ProgramResult {
+        if ctx.accounts.vesting_account.staking_pool != ctx.accounts.staking_pool.key() {
+            return Err(LinearVestingError::StakedInOtherPool.into());
+        }
+
+        if amount > ctx.accounts.vesting_account.staked_amount {
+            return Err(LinearVestingError::InsufficientStake.into());
+        }
 
-        let current_balance = ctx.accounts.vault_account.amount;
+        let authority = ctx.accounts.authority.key();
+        if authority == ctx.accounts.vesting_account.beneficiary {
+            let now = Clock::get()?.unix_timestamp;
+            let vested_unreleased = ctx
+                .accounts
+                .vesting_account
+                .vested_amount(now)?
+                .saturating_sub(ctx.accounts.vesting_account.released_amount);
+            if amount > vested_unreleased {
+                return Err(LinearVestingError::UnstakeExceedsVested.into());
+            }
+        } else if authority != ctx.accounts.vesting_account.owner {
+            return Err(LinearVestingError::Unauthorized.into());
+        }
 
-        let refund = current_balance;
-        ctx.accounts.vesting_account.revoked = true;
-        ctx.accounts.vesting_account.approved = false;
-        // ctx.accounts.vesting_account.released_amount += refund;
-        ctx.accounts.vesting_account.total_deposited_amount = ctx
+        ctx.accounts.vesting_account.staked_amount = ctx
             .accounts
-            .vesting_account.total_deposited_amount
-            .checked_sub(refund)
-            .unwrap();
+            .vesting_account
+            .staked_amount
+            .checked_sub(amount)
+            .ok_or(LinearVestingError::InsufficientStake)?;
+
+        let investor_account = ctx.accounts.vesting_account.investor_account;
+        let (_vault_authority, vault_authority_bump) =
+            find_vault_authority(&investor_account, ctx.program_id);
+        let authority_seeds = &[
+            &VAULT_AUTHORITY_PDA_SEED[..],
+            investor_account.as_ref(),
+            &[vault_authority_bump],
+        ];
+
+        let vault_before = token_account(&ctx.accounts.vault_account)?.amount;
+        staking::unstake_locked(&ctx.accounts.locked_stake(), amount, &[&authority_seeds[..]])?;
+        let received = token_account(&ctx.accounts.vault_account)?
+            .amount
+            .checked_sub(vault_before)
+            .ok_or(LinearVestingError::MathOverflow)?;
+
+        ctx.accounts.vesting_account.withhold_fee(amount.saturating_sub(received))
+    }
+
+    /// Closes a completed or revoked grant and its empty vault, refunding the rent to the owner.
+    /// Transfer fees withheld in a Token-2022 vault are harvested to the mint first.
+    pub fn close_vesting(
+        ctx: Context<CloseVesting>,
+    ) -> ProgramResult {
+        let status = ctx.accounts.vesting_account.status;
+        if status != VestingStatus::Completed && status != VestingStatus::Revoked {
+            return Err(LinearVestingError::GrantNotFinished.into());
+        }
+
+        if token_account(&ctx.accounts.vault_account)?.amount > 0 {
+            return Err(LinearVestingError::VaultNotEmpty.into());
+        }
 
+        let vesting_key = ctx.accounts.vesting_account.key();
+        let investor_account = &mut ctx.accounts.investor_account;
+        if let Some(i) = investor_account.grants.iter().position(|grant| *grant == vesting_key) {
+            investor_account.grants.swap_remove(i);
+            investor_account.investors.swap_remove(i);
+        }
+
+        let investor_account = ctx.accounts.vesting_account.investor_account;
         let (_vault_authority, vault_authority_bump) =
-            Pubkey::find_program_address(&[VAULT_AUTHORITY_PDA_SEED], ctx.program_id);
-        let authority_seeds = &[&VAULT_AUTHORITY_PDA_SEED[..], &[vault_authority_bump]];
-        
-        token::transfer(
-            ctx.accounts.into_transfer_to_owner_context().with_signer(&[&authority_seeds[..]]),
-            refund,
+            find_vault_authority(&investor_account, ctx.program_id);
+        let authority_seeds = &[
+            &VAULT_AUTHORITY_PDA_SEED[..],
+            investor_account.as_ref(),
+            &[vault_authority_bump],
+        ];
+
+        token_interface::harvest_withheld_tokens_to_mint(
+            ctx.accounts.token_program.clone(),
+            ctx.accounts.mint.clone(),
+            ctx.accounts.vault_account.clone(),
+        )?;
+        token_interface::close_account(
+            ctx.accounts.into_close_vault_context().with_signer(&[&authority_seeds[..]]),
         )?;
 
         Ok(())
@@ -281,12 +836,21 @@ pub mod solana_vesting {
 pub struct InitializeVesting<'info> {
     #[account(
         init,
-        seeds = [INVESTOR_ACCOUNT_PDA_SEED],
+        seeds = [
+            INVESTOR_ACCOUNT_PDA_SEED,
+            owner.key.as_ref(),
+            mint.to_account_info().key.as_ref(),
+        ],
         bump,
         payer = owner,
-        space = 8 * 25
+        space = INVESTOR_ACCOUNT_SPACE
     )]
     pub investor_account: Account<'info, InvestorAccount>,
+    #[account(
+        constraint = token_interface::mint(&mint).is_ok() @ LinearVestingError::InvalidMint,
+        constraint = token_interface::is_supported_mint(&mint)? @ LinearVestingError::UnsupportedMintExtension,
+    )]
+    pub mint: AccountInfo<'info>,
     pub owner: Signer<'info>,
     pub system_program: AccountInfo<'info>,
 }
@@ -295,7 +859,10 @@ pub struct InitializeVesting<'info> {
 pub struct EnableAccount<'info> {
     #[account(mut)]
     pub investor_account: Account<'info, InvestorAccount>,
-    #[account(mut)]
+    #[account(
+        mut,
+        has_one = investor_account,
+    )]
     pub vesting_account: Box<Account<'info, VestingAccount>>,
     pub owner: Signer<'info>,
     pub system_program: AccountInfo<'info>,
@@ -305,7 +872,10 @@ pub struct EnableAccount<'info> {
 pub struct DisableAccount<'info> {
     #[account(mut)]
     pub investor_account: Account<'info, InvestorAccount>,
-    #[account(mut)]
+    #[account(
+        mut,
+        has_one = investor_account,
+    )]
     pub vesting_account: Box<Account<'info, VestingAccount>>,
     pub owner: Signer<'info>,
     pub system_program: AccountInfo<'info>,
@@ -315,124 +885,435 @@ pub struct DisableAccount<'info> {
 pub struct RenameAccount<'info> {
     #[account(mut)]
     pub investor_account: Account<'info, InvestorAccount>,
-    #[account(mut)]
+    #[account(
+        mut,
+        has_one = investor_account,
+    )]
     pub vesting_account: Box<Account<'info, VestingAccount>>,
     pub owner: Signer<'info>,
     pub system_program: AccountInfo<'info>,
 }
 
 #[derive(Accounts)]
-#[instruction(amount: u64,
-    name: String,
-  start_ts: i64,
-  revocable: bool)]
-pub struct Initialize<'info> {
+pub struct SetTransferApproval<'info> {
+    pub investor_account: Account<'info, InvestorAccount>,
+    #[account(
+        mut,
+        has_one = investor_account,
+    )]
+    pub vesting_account: Box<Account<'info, VestingAccount>>,
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct TransferBeneficiary<'info> {
+    pub beneficiary: Signer<'info>,
+    pub new_beneficiary: AccountInfo<'info>,
+    /// The token account releases are paid to from now on
+    #[account(
+        constraint = token_account(&new_beneficiary_ata)?.owner == new_beneficiary.key(),
+        constraint = token_account(&new_beneficiary_ata)?.mint == vesting_account.mint @ LinearVestingError::InvalidMint,
+    )]
+    pub new_beneficiary_ata: AccountInfo<'info>,
     #[account(mut)]
+    pub investor_account: Account<'info, InvestorAccount>,
+    #[account(
+        mut,
+        has_one = beneficiary,
+        has_one = investor_account,
+        has_one = owner,
+    )]
+    pub vesting_account: Box<Account<'info, VestingAccount>>,
+    /// Signs only when the grant requires the owner's approval
     pub owner: AccountInfo<'info>,
-    pub beneficiary: AccountInfo<'info>,
-    pub mint: Account<'info, Mint>,
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64)]
+pub struct Initialize<'info> {
     #[account(mut)]
-    pub beneficiary_ata: Account<'info, TokenAccount>,
+    pub owner: Signer<'info>,
+    pub beneficiary: AccountInfo<'info>,
     #[account(
-        init,
-        seeds = [VAULT_PDA_SEED, &beneficiary_ata.to_account_info().key.to_bytes()], bump,
-        payer = owner,
-        token::mint = mint,
-        token::authority = owner,
+        constraint = mint.key() == investor_account.mint @ LinearVestingError::InvalidMint,
+    )]
+    pub mint: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = token_account(&beneficiary_ata)?.owner == beneficiary.key(),
+        constraint = token_account(&beneficiary_ata)?.mint == mint.key() @ LinearVestingError::InvalidMint,
+    )]
+    pub beneficiary_ata: AccountInfo<'info>,
+    /// Created by the instruction, sized for the extensions of the mint
+    #[account(
+        mut,
+        seeds = [VAULT_PDA_SEED, &vesting_account.to_account_info().key.to_bytes()], bump,
+    )]
+    pub vault_account: AccountInfo<'info>,
+    #[account(
+        seeds = [VAULT_AUTHORITY_PDA_SEED, investor_account.to_account_info().key.as_ref()],
+        bump,
     )]
-    pub vault_account: Account<'info, TokenAccount>,
+    pub vault_authority: AccountInfo<'info>,
     #[account(
         mut,
-        constraint = owner_token_account.amount >= amount
+        constraint = token_account(&owner_token_account)?.amount >= amount,
+        constraint = token_account(&owner_token_account)?.mint == mint.key() @ LinearVestingError::InvalidMint,
     )]
-    pub owner_token_account: Account<'info, TokenAccount>,
+    pub owner_token_account: AccountInfo<'info>,
     #[account(
         init,
-        seeds = [&beneficiary_ata.to_account_info().key.to_bytes()],
+        seeds = [
+            investor_account.to_account_info().key.as_ref(),
+            beneficiary_ata.to_account_info().key.as_ref(),
+            investor_account.grant_count.to_le_bytes().as_ref(),
+        ],
         bump,
         payer = owner,
-        space = 8 * 25
+        space = VESTING_ACCOUNT_SPACE
     )]
     pub vesting_account: Box<Account<'info, VestingAccount>>,
+    #[account(
+        mut,
+        has_one = owner,
+    )]
+    pub investor_account: Account<'info, InvestorAccount>,
+    #[account(address = system_program::ID)]
+    pub system_program: AccountInfo<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    #[account(
+        constraint = token_interface::is_token_program(token_program.key) @ LinearVestingError::InvalidTokenProgram,
+        constraint = *mint.owner == token_program.key() @ LinearVestingError::InvalidTokenProgram,
+    )]
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeBatch<'info> {
     #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        constraint = mint.key() == investor_account.mint @ LinearVestingError::InvalidMint,
+    )]
+    pub mint: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = token_account(&owner_token_account)?.mint == mint.key() @ LinearVestingError::InvalidMint,
+    )]
+    pub owner_token_account: AccountInfo<'info>,
+    #[account(
+        mut,
+        has_one = owner,
+    )]
     pub investor_account: Account<'info, InvestorAccount>,
+    #[account(
+        seeds = [VAULT_AUTHORITY_PDA_SEED, investor_account.key().as_ref()],
+        bump,
+    )]
+    pub vault_authority: AccountInfo<'info>,
+    #[account(address = system_program::ID)]
     pub system_program: AccountInfo<'info>,
     pub rent: Sysvar<'info, Rent>,
+    #[account(
+        constraint = token_interface::is_token_program(token_program.key) @ LinearVestingError::InvalidTokenProgram,
+        constraint = *mint.owner == token_program.key() @ LinearVestingError::InvalidTokenProgram,
+    )]
     pub token_program: AccountInfo<'info>,
 }
 
+/// One periodic grant of an `initialize_batch`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct BatchGrant {
+    pub amount: u64,
+    pub name: String,
+    pub start_ts: i64,
+    pub cliff_ts: i64,
+    pub duration: i64,
+    pub upfront_bps: u64,
+    pub withdraw_period: i64,
+    pub revocable: bool,
+}
+
 #[account]
 pub struct InvestorAccount {
     /// The investor who will received tokens
     pub investors: Vec<Pubkey>,
     /// Owner that can revoke the account
-    pub owner: Pubkey
+    pub owner: Pubkey,
+    /// The mint of the SPL token granted in this project
+    pub mint: Pubkey,
+    /// Number of grants created, used as the id of the next grant
+    pub grant_count: u64,
+    /// The vesting account of each grant, in the same order as `investors`
+    pub grants: Vec<Pubkey>
 }
 
-#[derive(Accounts)]
-pub struct AddTokenToVestingAccount<'info> {
-    #[account(mut)]
-    pub owner: AccountInfo<'info>,
-    #[account(mut)]
-    pub vault_account: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub vesting_account: Box<Account<'info, VestingAccount>>,
+impl InvestorAccount {
+    /// The vesting accounts of every grant held by `beneficiary`
+    pub fn grants_of<'a>(&'a self, beneficiary: &'a Pubkey) -> impl Iterator<Item = &'a Pubkey> + 'a {
+        self.investors
+            .iter()
+            .zip(self.grants.iter())
+            .filter(move |(investor, _)| *investor == beneficiary)
+            .map(|(_, grant)| grant)
+    }
+
+    /// Registers the grant `grant_count` of the project, held by `beneficiary`
+    fn add_grant(&mut self, beneficiary: Pubkey, vesting_account: Pubkey) -> ProgramResult {
+        if self.grants.len() >= MAX_PROJECT_GRANTS {
+            return Err(LinearVestingError::TooManyGrants.into());
+        }
+
+        self.investors.push(beneficiary);
+        self.grants.push(vesting_account);
+        self.grant_count = self
+            .grant_count
+            .checked_add(1)
+            .ok_or(LinearVestingError::MathOverflow)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct AddTokenToVestingAccount<'info> {
     #[account(mut)]
-    pub owner_token_account: Account<'info, TokenAccount>,
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [VAULT_PDA_SEED, vesting_account.key().as_ref()],
+        bump,
+    )]
+    pub vault_account: AccountInfo<'info>,
+    #[account(
+        mut,
+        has_one = owner,
+    )]
+    pub vesting_account: Box<Account<'info, VestingAccount>>,
+    #[account(
+        constraint = mint.key() == vesting_account.mint @ LinearVestingError::InvalidMint,
+    )]
+    pub mint: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = token_account(&owner_token_account)?.mint == vesting_account.mint @ LinearVestingError::InvalidMint,
+    )]
+    pub owner_token_account: AccountInfo<'info>,
     pub system_program: AccountInfo<'info>,
     pub rent: Sysvar<'info, Rent>,
+    #[account(
+        constraint = token_interface::is_token_program(token_program.key) @ LinearVestingError::InvalidTokenProgram,
+        constraint = *mint.owner == token_program.key() @ LinearVestingError::InvalidTokenProgram,
+    )]
     pub token_program: AccountInfo<'info>,
 }
 
 #[derive(Accounts)]
 pub struct Upfront<'info> {
-    #[account(mut)]
-    pub beneficiary: AccountInfo<'info>,
-    #[account(mut)]
-    pub beneficiary_ata: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub vault_account: Account<'info, TokenAccount>,
-    #[account(mut)]
+    pub beneficiary: Signer<'info>,
+    #[account(
+        mut,
+        constraint = beneficiary_ata.key() == vesting_account.beneficiary_ata @ LinearVestingError::InvalidBeneficiaryAta,
+        constraint = token_account(&beneficiary_ata)?.owner == beneficiary.key(),
+        constraint = token_account(&beneficiary_ata)?.mint == vesting_account.mint @ LinearVestingError::InvalidMint,
+    )]
+    pub beneficiary_ata: AccountInfo<'info>,
+    #[account(
+        mut,
+        seeds = [VAULT_PDA_SEED, vesting_account.key().as_ref()],
+        bump,
+    )]
+    pub vault_account: AccountInfo<'info>,
+    #[account(
+        mut,
+        has_one = beneficiary,
+    )]
     pub vesting_account: Box<Account<'info, VestingAccount>>,
+    #[account(
+        constraint = mint.key() == vesting_account.mint @ LinearVestingError::InvalidMint,
+    )]
+    pub mint: AccountInfo<'info>,
+    #[account(
+        seeds = [VAULT_AUTHORITY_PDA_SEED, vesting_account.investor_account.as_ref()],
+        bump,
+    )]
     pub vault_authority: AccountInfo<'info>,
-    #[account(mut)]
-    pub owner_token_account: Account<'info, TokenAccount>,
     pub system_program: AccountInfo<'info>,
     pub rent: Sysvar<'info, Rent>,
+    #[account(
+        constraint = token_interface::is_token_program(token_program.key) @ LinearVestingError::InvalidTokenProgram,
+        constraint = *mint.owner == token_program.key() @ LinearVestingError::InvalidTokenProgram,
+    )]
     pub token_program: AccountInfo<'info>,
 }
 
 #[derive(Accounts)]
 pub struct Withdraw<'info> {
-    #[account(mut)]
-    pub beneficiary: AccountInfo<'info>,
-    #[account(mut)]
-    pub beneficiary_ata: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub vault_account: Account<'info, TokenAccount>,
-    #[account(mut)]
+    pub beneficiary: Signer<'info>,
+    #[account(
+        mut,
+        constraint = beneficiary_ata.key() == vesting_account.beneficiary_ata @ LinearVestingError::InvalidBeneficiaryAta,
+        constraint = token_account(&beneficiary_ata)?.owner == beneficiary.key(),
+        constraint = token_account(&beneficiary_ata)?.mint == vesting_account.mint @ LinearVestingError::InvalidMint,
+    )]
+    pub beneficiary_ata: AccountInfo<'info>,
+    #[account(
+        mut,
+        seeds = [VAULT_PDA_SEED, vesting_account.key().as_ref()],
+        bump,
+    )]
+    pub vault_account: AccountInfo<'info>,
+    #[account(
+        mut,
+        has_one = beneficiary,
+    )]
     pub vesting_account: Box<Account<'info, VestingAccount>>,
+    #[account(
+        constraint = mint.key() == vesting_account.mint @ LinearVestingError::InvalidMint,
+    )]
+    pub mint: AccountInfo<'info>,
+    #[account(
+        seeds = [VAULT_AUTHORITY_PDA_SEED, vesting_account.investor_account.as_ref()],
+        bump,
+    )]
     pub vault_authority: AccountInfo<'info>,
-    #[account(mut)]
-    pub owner_token_account: Account<'info, TokenAccount>,
     pub system_program: AccountInfo<'info>,
     pub rent: Sysvar<'info, Rent>,
+    #[account(
+        constraint = token_interface::is_token_program(token_program.key) @ LinearVestingError::InvalidTokenProgram,
+        constraint = *mint.owner == token_program.key() @ LinearVestingError::InvalidTokenProgram,
+    )]
     pub token_program: AccountInfo<'info>,
 }
 
 #[derive(Accounts)]
 pub struct Revoke<'info> {
     #[account(mut)]
-    pub owner: AccountInfo<'info>,
-    #[account(mut)]
-    pub vault_account: Account<'info, TokenAccount>,
-    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [VAULT_PDA_SEED, vesting_account.key().as_ref()],
+        bump,
+    )]
+    pub vault_account: AccountInfo<'info>,
+    #[account(
+        mut,
+        has_one = owner,
+    )]
     pub vesting_account: Box<Account<'info, VestingAccount>>,
-    #[account(mut)]
-    pub owner_token_account: Account<'info, TokenAccount>,
+    #[account(
+        constraint = mint.key() == vesting_account.mint @ LinearVestingError::InvalidMint,
+    )]
+    pub mint: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = token_account(&owner_token_account)?.owner == owner.key(),
+        constraint = token_account(&owner_token_account)?.mint == vesting_account.mint @ LinearVestingError::InvalidMint,
+    )]
+    pub owner_token_account: AccountInfo<'info>,
+    /// Receives the part of the grant vested before the revocation
+    #[account(
+        mut,
+        constraint = beneficiary_ata.key() == vesting_account.beneficiary_ata @ LinearVestingError::InvalidBeneficiaryAta,
+        constraint = token_account(&beneficiary_ata)?.owner == vesting_account.beneficiary,
+        constraint = token_account(&beneficiary_ata)?.mint == vesting_account.mint @ LinearVestingError::InvalidMint,
+    )]
+    pub beneficiary_ata: AccountInfo<'info>,
+    #[account(
+        seeds = [VAULT_AUTHORITY_PDA_SEED, vesting_account.investor_account.as_ref()],
+        bump,
+    )]
     pub vault_authority: AccountInfo<'info>,
     pub system_program: AccountInfo<'info>,
     pub rent: Sysvar<'info, Rent>,
+    #[account(
+        constraint = token_interface::is_token_program(token_program.key) @ LinearVestingError::InvalidTokenProgram,
+        constraint = *mint.owner == token_program.key() @ LinearVestingError::InvalidTokenProgram,
+    )]
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct StakeVesting<'info> {
+    /// The beneficiary, or the owner when unstaking
+    pub authority: Signer<'info>,
+    #[account(mut)]
+    pub vesting_account: Box<Account<'info, VestingAccount>>,
+    #[account(
+        mut,
+        seeds = [VAULT_PDA_SEED, vesting_account.key().as_ref()],
+        bump,
+    )]
+    pub vault_account: AccountInfo<'info>,
+    #[account(
+        constraint = mint.key() == vesting_account.mint @ LinearVestingError::InvalidMint,
+    )]
+    pub mint: AccountInfo<'info>,
+    #[account(
+        seeds = [VAULT_AUTHORITY_PDA_SEED, vesting_account.investor_account.as_ref()],
+        bump,
+    )]
+    pub vault_authority: AccountInfo<'info>,
+    #[account(
+        constraint = beneficiary.key() == vesting_account.beneficiary,
+    )]
+    pub beneficiary: AccountInfo<'info>,
+    // The staking accounts are checked by the staking program
+    #[account(mut)]
+    pub staking_pool: AccountInfo<'info>,
+    #[account(mut)]
+    pub staking_vault: AccountInfo<'info>,
+    pub staking_reward_vault: AccountInfo<'info>,
+    /// The beneficiary's user account in the staking pool
+    #[account(mut)]
+    pub staking_user: AccountInfo<'info>,
+    pub staking_pool_signer: AccountInfo<'info>,
+    #[account(address = staking::staking_program::ID)]
+    pub staking_program: AccountInfo<'info>,
+    #[account(
+        constraint = token_interface::is_token_program(token_program.key) @ LinearVestingError::InvalidTokenProgram,
+        constraint = *mint.owner == token_program.key() @ LinearVestingError::InvalidTokenProgram,
+    )]
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseVesting<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        has_one = owner,
+    )]
+    pub investor_account: Account<'info, InvestorAccount>,
+    #[account(
+        mut,
+        has_one = owner,
+        has_one = investor_account,
+        close = owner,
+    )]
+    pub vesting_account: Box<Account<'info, VestingAccount>>,
+    #[account(
+        mut,
+        seeds = [VAULT_PDA_SEED, vesting_account.key().as_ref()],
+        bump,
+    )]
+    pub vault_account: AccountInfo<'info>,
+    /// Receives the transfer fees withheld in the vault
+    #[account(
+        mut,
+        constraint = mint.key() == vesting_account.mint @ LinearVestingError::InvalidMint,
+    )]
+    pub mint: AccountInfo<'info>,
+    #[account(
+        seeds = [VAULT_AUTHORITY_PDA_SEED, vesting_account.investor_account.as_ref()],
+        bump,
+    )]
+    pub vault_authority: AccountInfo<'info>,
+    #[account(
+        constraint = token_interface::is_token_program(token_program.key) @ LinearVestingError::InvalidTokenProgram,
+        constraint = *mint.owner == token_program.key() @ LinearVestingError::InvalidTokenProgram,
+    )]
     pub token_program: AccountInfo<'info>,
 }
 
@@ -441,16 +1322,28 @@ pub struct Revoke<'info> {
 pub struct VestingAccount {
     /// The investor who will received vested tokens
     pub beneficiary: Pubkey,
+    /// The token account releases are paid to
+    pub beneficiary_ata: Pubkey,
+    /// The investor account (project) the grant belongs to
+    pub investor_account: Pubkey,
+    /// Id of the grant within the investor account
+    pub grant_id: u64,
     /// The timestamp for when the lock ends and vesting begins
     pub start_ts: i64,
     /// The timestamp for when withdraw
     pub withdraw_ts: i64,
     /// The timestamp for when the cliff ends (vesting happens during cliff!)
     pub cliff_ts: i64,
-    /// The duration of the vesting period
+    /// The duration of the vesting period, counted from `start_ts`
     pub duration: i64,
+    /// Share of the total released up front, in basis points
+    pub upfront_bps: u64,
+    /// Minimum time between two withdrawals
+    pub withdraw_period: i64,
     /// Whether this vesting account is revocable
     pub revocable: bool,
+    /// Whether moving the grant to a new beneficiary needs the owner to co-sign
+    pub transfer_requires_approval: bool,
     /// Owner that can revoke the account
     pub owner: Pubkey,
     /// The name of an investor
@@ -461,51 +1354,738 @@ pub struct VestingAccount {
     pub total_deposited_amount: u64,
     /// Amount that has been released
     pub released_amount: u64,
-    /// Claimed count
-    pub claimed_count: u8,
-    /// The account is revoked
-    pub revoked: bool,
+    /// Number of releases paid out to the beneficiary
+    pub claimed_count: u32,
+    /// Where the grant is in its lifecycle
+    pub status: VestingStatus,
+    /// When the grant was revoked and its schedule frozen
+    pub revoked_ts: i64,
+    /// When the grant was last paused
+    pub paused_ts: i64,
+    /// Whether resuming the grant pushes its schedule back by the paused time
+    pub shift_on_resume: bool,
     /// upfronted
     pub upfronted: bool,
-    /// Approved status
-    pub approved: bool
+    /// How the grant is released
+    pub schedule_kind: ScheduleKind,
+    /// Unlock dates and amounts of a milestone schedule
+    pub tranches: Vec<Tranche>,
+    /// Deposits added on top of a periodic schedule after it started
+    pub top_ups: Vec<TopUp>,
+    /// The staking pool the grant has tokens staked in
+    pub staking_pool: Pubkey,
+    /// Unvested tokens of the grant staked in `staking_pool`
+    pub staked_amount: u64
+}
+
+/// How a vesting account releases its tokens
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub enum ScheduleKind {
+    /// An upfront share, then linear releases every `withdraw_period`
+    Periodic,
+    /// Fixed amounts unlocking at fixed dates
+    Milestone,
+}
+
+impl Default for ScheduleKind {
+    fn default() -> Self {
+        ScheduleKind::Periodic
+    }
+}
+
+/// The lifecycle of a grant
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub enum VestingStatus {
+    /// Releasing tokens on schedule
+    Active,
+    /// Temporarily blocked by the owner, can be resumed
+    Paused,
+    /// Settled and closed by the owner, can never be resumed
+    Revoked,
+    /// Everything deposited has been released
+    Completed,
+}
+
+impl Default for VestingStatus {
+    fn default() -> Self {
+        VestingStatus::Active
+    }
+}
+
+impl VestingStatus {
+    /// Whether a grant in this status may move to `to`
+    pub fn can_transition_to(self, to: VestingStatus) -> bool {
+        matches!(
+            (self, to),
+            (VestingStatus::Active, VestingStatus::Paused)
+                | (VestingStatus::Paused, VestingStatus::Active)
+                | (VestingStatus::Active, VestingStatus::Revoked)
+                | (VestingStatus::Paused, VestingStatus::Revoked)
+                | (VestingStatus::Active, VestingStatus::Completed)
+                | (VestingStatus::Completed, VestingStatus::Active)
+        )
+    }
+}
+
+#[event]
+pub struct VestingStatusChanged {
+    pub vesting_account: Pubkey,
+    pub from: VestingStatus,
+    pub to: VestingStatus,
+    pub timestamp: i64,
+}
+
+/// Why tokens were paid out to the beneficiary
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub enum ReleaseKind {
+    /// The upfront share of a periodic schedule
+    Upfront,
+    /// Tokens vested since the last release
+    Withdraw,
+    /// Tokens vested but not yet released when the grant was revoked
+    Revoke,
+}
+
+/// Tokens paid out of the vault to the beneficiary
+#[event]
+pub struct TokensReleased {
+    pub vesting_account: Pubkey,
+    pub beneficiary: Pubkey,
+    pub kind: ReleaseKind,
+    pub amount: u64,
+    /// Everything released from the grant so far, this release included
+    pub released_amount: u64,
+    pub claimed_count: u32,
+    pub timestamp: i64,
+}
+
+/// Tokens added to the grant by the owner
+#[event]
+pub struct TokensDeposited {
+    pub vesting_account: Pubkey,
+    pub amount: u64,
+    /// Everything deposited into the grant so far, this deposit included
+    pub total_deposited_amount: u64,
+    pub timestamp: i64,
+}
+
+/// Unvested tokens returned to the owner when the grant was revoked
+#[event]
+pub struct TokensRefunded {
+    pub vesting_account: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+/// An amount that unlocks at a given time
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default)]
+pub struct Tranche {
+    pub unlock_ts: i64,
+    pub amount: u64,
+}
+
+/// An amount vesting linearly from `start_ts` to `end_ts`, on the withdraw periods of the grant
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default)]
+pub struct TopUp {
+    pub start_ts: i64,
+    pub end_ts: i64,
+    pub amount: u64,
+}
+
+impl TopUp {
+    /// The amount vested at the end of the withdraw period `period_end_ts`
+    fn vested_amount(&self, period_end_ts: i64) -> Result<u64, ProgramError> {
+        let duration = self
+            .end_ts
+            .checked_sub(self.start_ts)
+            .ok_or(LinearVestingError::MathOverflow)?;
+        let elapsed = period_end_ts
+            .checked_sub(self.start_ts)
+            .ok_or(LinearVestingError::MathOverflow)?
+            .clamp(0, duration);
+
+        Ok((self.amount as u128 * elapsed as u128 / duration as u128) as u64)
+    }
+}
+
+/// How a top-up is added to the schedule of a grant
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub enum TopUpPolicy {
+    /// Spread over what is left of the schedule: the rest of the vesting period of a periodic
+    /// schedule, the tranches not unlocked yet of a milestone schedule, in proportion to them
+    ProRata,
+    /// A tranche of its own: vesting over the grant's duration from `start_ts` for a periodic
+    /// schedule, unlocking at `start_ts`, which must be in the future, for a milestone schedule
+    NewTranche { start_ts: i64 },
+}
+
+impl VestingAccount {
+    /// The amount released up front, before linear vesting starts paying out
+    pub fn upfront_amount(&self) -> u64 {
+        if self.schedule_kind != ScheduleKind::Periodic {
+            return 0;
+        }
+
+        (self.base_amount() as u128 * self.upfront_bps as u128 / BPS_DENOMINATOR as u128) as u64
+    }
+
+    /// The deposit following the upfront + periodic terms, without the top-ups
+    fn base_amount(&self) -> u64 {
+        // top-ups are part of the deposit, so their sum saturating only shrinks the base to 0
+        let top_ups = self
+            .top_ups
+            .iter()
+            .fold(0u64, |sum, top_up| sum.saturating_add(top_up.amount));
+        self.total_deposited_amount.saturating_sub(top_ups)
+    }
+
+    /// The end of the upfront + periodic schedule, without the top-ups
+    fn end_ts(&self) -> Result<i64, ProgramError> {
+        self.start_ts
+            .checked_add(self.duration)
+            .ok_or_else(|| LinearVestingError::MathOverflow.into())
+    }
+
+    /// The amount vested at `current_ts`
+    pub fn vested_amount(&self, current_ts: i64) -> Result<u64, ProgramError> {
+        if self.status == VestingStatus::Revoked {
+            // the schedule was settled and frozen at `revoked_ts`
+            return Ok(self.total_deposited_amount);
+        }
+
+        match self.schedule_kind {
+            ScheduleKind::Periodic => self.periodic_vested_amount(current_ts),
+            ScheduleKind::Milestone => self
+                .tranches
+                .iter()
+                .filter(|tranche| tranche.unlock_ts <= current_ts)
+                .try_fold(0u64, |sum, tranche| sum.checked_add(tranche.amount))
+                .ok_or_else(|| LinearVestingError::MathOverflow.into()),
+        }
+    }
+
+    /// Sets the terms of an upfront + periodic schedule
+    pub fn set_periodic_schedule(
+        &mut self,
+        start_ts: i64,
+        cliff_ts: i64,
+        duration: i64,
+        upfront_bps: u64,
+        withdraw_period: i64,
+    ) {
+        self.schedule_kind = ScheduleKind::Periodic;
+        self.start_ts = start_ts;
+        self.withdraw_ts = start_ts;
+        self.cliff_ts = cliff_ts;
+        self.duration = duration;
+        self.upfront_bps = upfront_bps;
+        self.withdraw_period = withdraw_period;
+        self.upfronted = false;
+    }
+
+    /// Moves the grant to `status`, rejecting transitions the lifecycle doesn't allow
+    pub fn set_status(&mut self, vesting_account: Pubkey, status: VestingStatus, now: i64) -> ProgramResult {
+        if !self.status.can_transition_to(status) {
+            return Err(LinearVestingError::InvalidStatusTransition.into());
+        }
+
+        emit!(VestingStatusChanged {
+            vesting_account,
+            from: self.status,
+            to: status,
+            timestamp: now,
+        });
+
+        self.status = status;
+        Ok(())
+    }
+
+    /// Books `amount` paid out to the beneficiary
+    pub fn record_release(
+        &mut self,
+        vesting_account: Pubkey,
+        kind: ReleaseKind,
+        amount: u64,
+        now: i64,
+    ) -> ProgramResult {
+        self.released_amount = self
+            .released_amount
+            .checked_add(amount)
+            .ok_or(LinearVestingError::MathOverflow)?;
+        self.claimed_count = self
+            .claimed_count
+            .checked_add(1)
+            .ok_or(LinearVestingError::MathOverflow)?;
+
+        emit!(TokensReleased {
+            vesting_account,
+            beneficiary: self.beneficiary,
+            kind,
+            amount,
+            released_amount: self.released_amount,
+            claimed_count: self.claimed_count,
+            timestamp: now,
+        });
+        Ok(())
+    }
+
+    /// Books `amount` deposited into the vault by the owner
+    pub fn record_deposit(&mut self, vesting_account: Pubkey, amount: u64, now: i64) -> ProgramResult {
+        self.total_deposited_amount = self
+            .total_deposited_amount
+            .checked_add(amount)
+            .ok_or(LinearVestingError::MathOverflow)?;
+
+        emit!(TokensDeposited {
+            vesting_account,
+            amount,
+            total_deposited_amount: self.total_deposited_amount,
+            timestamp: now,
+        });
+        Ok(())
+    }
+
+    /// Takes `fee`, withheld by the token program from tokens moved for the grant, off what
+    /// the grant vests
+    pub fn withhold_fee(&mut self, fee: u64) -> ProgramResult {
+        if fee == 0 {
+            return Ok(());
+        }
+
+        self.total_deposited_amount = self
+            .total_deposited_amount
+            .checked_sub(fee)
+            .ok_or(LinearVestingError::MathOverflow)?;
+        self.deduct_from_tranches(fee);
+        Ok(())
+    }
+
+    /// Takes `amount` off the last tranches of a milestone schedule
+    fn deduct_from_tranches(&mut self, mut amount: u64) {
+        if self.schedule_kind != ScheduleKind::Milestone {
+            return;
+        }
+
+        for tranche in self.tranches.iter_mut().rev() {
+            let deducted = amount.min(tranche.amount);
+            tranche.amount -= deducted;
+            amount -= deducted;
+        }
+    }
+
+    /// Marks the grant completed once everything deposited has been released
+    fn complete_if_released(&mut self, vesting_account: Pubkey, now: i64) -> ProgramResult {
+        if self.released_amount < self.total_deposited_amount {
+            return Ok(());
+        }
+
+        self.set_status(vesting_account, VestingStatus::Completed, now)
+    }
+
+    /// Pushes every date of the schedule back by `delay`
+    pub fn shift_schedule(&mut self, delay: i64) -> ProgramResult {
+        let shift = |ts: i64| ts.checked_add(delay).ok_or(LinearVestingError::MathOverflow);
+
+        self.start_ts = shift(self.start_ts)?;
+        self.cliff_ts = shift(self.cliff_ts)?;
+        self.withdraw_ts = shift(self.withdraw_ts)?;
+        for tranche in self.tranches.iter_mut() {
+            tranche.unlock_ts = shift(tranche.unlock_ts)?;
+        }
+        for top_up in self.top_ups.iter_mut() {
+            top_up.start_ts = shift(top_up.start_ts)?;
+            top_up.end_ts = shift(top_up.end_ts)?;
+        }
+
+        Ok(())
+    }
+
+    /// Ends a pause at `now`, shifting the schedule by the paused time if the pause asked for it
+    pub fn settle_pause(&mut self, now: i64) -> ProgramResult {
+        if self.shift_on_resume {
+            let paused_duration = now
+                .checked_sub(self.paused_ts)
+                .ok_or(LinearVestingError::MathOverflow)?;
+            self.shift_schedule(paused_duration)?;
+        }
+
+        Ok(())
+    }
+
+    /// Adds the schedule of a top-up of `amount`; the deposit itself is booked by `record_deposit`
+    pub fn add_top_up(&mut self, amount: u64, policy: TopUpPolicy, now: i64) -> ProgramResult {
+        match (self.schedule_kind, policy) {
+            (ScheduleKind::Periodic, TopUpPolicy::ProRata) => {
+                // nothing vested yet: the whole schedule grows, upfront share included
+                if !self.upfronted && now <= self.start_ts {
+                    return Ok(());
+                }
+
+                let end_ts = self.end_ts()?;
+                let start_ts = now.max(self.start_ts);
+                if start_ts >= end_ts {
+                    return Err(LinearVestingError::NoRemainingSchedule.into());
+                }
+                self.push_top_up(TopUp { start_ts, end_ts, amount })
+            }
+            (ScheduleKind::Periodic, TopUpPolicy::NewTranche { start_ts }) => {
+                if start_ts < self.start_ts {
+                    return Err(LinearVestingError::InvalidTopUp.into());
+                }
+                let end_ts = start_ts
+                    .checked_add(self.duration)
+                    .ok_or(LinearVestingError::MathOverflow)?;
+                self.push_top_up(TopUp { start_ts, end_ts, amount })
+            }
+            (ScheduleKind::Milestone, TopUpPolicy::ProRata) => self.spread_over_tranches(amount, now),
+            (ScheduleKind::Milestone, TopUpPolicy::NewTranche { start_ts }) => {
+                // a tranche unlocking now or earlier would be claimable at once
+                if start_ts <= now {
+                    return Err(LinearVestingError::TrancheInPast.into());
+                }
+                self.insert_tranche(Tranche { unlock_ts: start_ts, amount })
+            }
+        }
+    }
+
+    fn push_top_up(&mut self, top_up: TopUp) -> ProgramResult {
+        if self.top_ups.len() >= MAX_TOP_UPS {
+            return Err(LinearVestingError::TooManyTopUps.into());
+        }
+
+        self.top_ups.push(top_up);
+        Ok(())
+    }
+
+    /// Adds `amount` to the tranches still locked at `now`, in proportion to their amounts
+    fn spread_over_tranches(&mut self, amount: u64, now: i64) -> ProgramResult {
+        let locked: u128 = self
+            .tranches
+            .iter()
+            .filter(|tranche| tranche.unlock_ts > now)
+            .map(|tranche| tranche.amount as u128)
+            .sum();
+        if locked == 0 {
+            return Err(LinearVestingError::NoRemainingSchedule.into());
+        }
+
+        let mut left = amount;
+        let last = self.tranches.len() - 1;
+        for (i, tranche) in self.tranches.iter_mut().enumerate() {
+            if tranche.unlock_ts <= now {
+                continue;
+            }
+
+            // the last tranche takes what rounding left over
+            let share = if i == last {
+                left
+            } else {
+                (amount as u128 * tranche.amount as u128 / locked) as u64
+            };
+            tranche.amount = tranche
+                .amount
+                .checked_add(share)
+                .ok_or(LinearVestingError::MathOverflow)?;
+            left -= share;
+        }
+
+        Ok(())
+    }
+
+    /// Adds a tranche, or to the tranche unlocking at the same time, keeping them in order
+    fn insert_tranche(&mut self, new_tranche: Tranche) -> ProgramResult {
+        if let Some(tranche) = self
+            .tranches
+            .iter_mut()
+            .find(|tranche| tranche.unlock_ts == new_tranche.unlock_ts)
+        {
+            tranche.amount = tranche
+                .amount
+                .checked_add(new_tranche.amount)
+                .ok_or(LinearVestingError::MathOverflow)?;
+            return Ok(());
+        }
+
+        if self.tranches.len() >= MAX_TRANCHES {
+            return Err(LinearVestingError::TooManyTranches.into());
+        }
+
+        let position = self
+            .tranches
+            .iter()
+            .position(|tranche| tranche.unlock_ts > new_tranche.unlock_ts)
+            .unwrap_or(self.tranches.len());
+        self.tranches.insert(position, new_tranche);
+
+        let first_unlock_ts = self.tranches[0].unlock_ts;
+        let last_unlock_ts = self.tranches[self.tranches.len() - 1].unlock_ts;
+        self.start_ts = first_unlock_ts;
+        self.cliff_ts = first_unlock_ts;
+        self.duration = last_unlock_ts - first_unlock_ts;
+        Ok(())
+    }
+
+    /// The end of the last withdraw period completed by `current_ts`, counted on the grid
+    /// of periods from `start_ts` and capped at the end of the schedule, top-ups included
+    pub fn last_period_end_ts(&self, current_ts: i64) -> Result<i64, ProgramError> {
+        let end_ts = self
+            .top_ups
+            .iter()
+            .map(|top_up| top_up.end_ts)
+            .fold(self.end_ts()?, i64::max);
+
+        self.period_end_ts(current_ts, end_ts)
+    }
+
+    /// The end of the last withdraw period completed by `current_ts`, or `end_ts` once passed
+    fn period_end_ts(&self, current_ts: i64, end_ts: i64) -> Result<i64, ProgramError> {
+        if current_ts >= end_ts {
+            return Ok(end_ts);
+        }
+
+        let elapsed = current_ts
+            .checked_sub(self.start_ts)
+            .ok_or(LinearVestingError::MathOverflow)?
+            .max(0);
+        let periods = elapsed
+            .checked_div(self.withdraw_period)
+            .ok_or(LinearVestingError::MathOverflow)?;
+
+        periods
+            .checked_mul(self.withdraw_period)
+            .and_then(|elapsed_periods| self.start_ts.checked_add(elapsed_periods))
+            .ok_or_else(|| LinearVestingError::MathOverflow.into())
+    }
+
+    /// The upfront share plus the rest vesting linearly from `start_ts` over `duration`,
+    /// one whole withdraw period at a time, and the part of each top-up vested alike
+    fn periodic_vested_amount(&self, current_ts: i64) -> Result<u64, ProgramError> {
+        let upfront = self.upfront_amount();
+        let elapsed = self
+            .period_end_ts(current_ts, self.end_ts()?)?
+            .checked_sub(self.start_ts)
+            .ok_or(LinearVestingError::MathOverflow)?;
+        let linear = (self.base_amount().saturating_sub(upfront) as u128)
+            .checked_mul(elapsed as u128)
+            .and_then(|vested| vested.checked_div(self.duration as u128))
+            .ok_or(LinearVestingError::MathOverflow)?;
+
+        let mut vested = upfront
+            .checked_add(linear as u64)
+            .ok_or(LinearVestingError::MathOverflow)?;
+        for top_up in self.top_ups.iter() {
+            let period_end_ts = self.period_end_ts(current_ts, top_up.end_ts)?;
+            vested = vested
+                .checked_add(top_up.vested_amount(period_end_ts)?)
+                .ok_or(LinearVestingError::MathOverflow)?;
+        }
+
+        Ok(vested)
+    }
 }
 
 impl<'info> Initialize<'info> {
-    fn into_transfer_to_pda_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
-        let cpi_accounts = Transfer {
-            from: self.owner_token_account.to_account_info().clone(),
-            to: self.vault_account.to_account_info().clone(),
-            authority: self.owner.clone(),
+    /// Fills in the parts of a grant shared by every schedule, registers the beneficiary,
+    /// creates the vault and moves the deposit into it. Only what reaches the vault after
+    /// transfer fees is granted.
+    fn register_grant(
+        &mut self,
+        program_id: &Pubkey,
+        amount: u64,
+        name: String,
+        revocable: bool,
+    ) -> ProgramResult {
+        validate_name(&name)?;
+        self.vesting_account.revocable = revocable;
+        self.vesting_account.name = name;
+
+        self.vesting_account.beneficiary = *self.beneficiary.key;
+        self.vesting_account.beneficiary_ata = *self.beneficiary_ata.key;
+        self.vesting_account.owner = *self.owner.key;
+        self.vesting_account.mint = *self.mint.key;
+
+        let vesting_key = self.vesting_account.key();
+        let (_vault_key, vault_bump) = find_vault_account(&vesting_key, program_id);
+        create_vault(
+            &self.owner.to_account_info(),
+            &self.vault_account,
+            token_interface::account_space(&self.mint, &self.token_program)?,
+            &vesting_key,
+            vault_bump,
+            &self.mint,
+            &self.vault_authority,
+            &self.system_program,
+            &self.rent,
+            &self.token_program,
+        )?;
+
+        let decimals = token_interface::mint(&self.mint)?.decimals;
+        token_interface::transfer_checked(self.into_transfer_to_pda_context(), amount, decimals)?;
+        let received = token_account(&self.vault_account)?.amount;
+        if received == 0 {
+            return Err(LinearVestingError::NoTokens.into());
+        }
+
+        self.vesting_account.total_deposited_amount = 0;
+        self.vesting_account.released_amount = 0;
+        self.vesting_account.claimed_count = 0;
+        self.vesting_account.status = VestingStatus::Active;
+        self.vesting_account.record_deposit(vesting_key, received, Clock::get()?.unix_timestamp)?;
+        self.vesting_account.deduct_from_tranches(amount - received);
+
+        self.vesting_account.investor_account = self.investor_account.key();
+        self.vesting_account.grant_id = self.investor_account.grant_count;
+
+        // register an investor to list
+        self.investor_account
+            .add_grant(*self.beneficiary.key, self.vesting_account.key())?;
+
+        Ok(())
+    }
+
+    fn into_transfer_to_pda_context(&self) -> CpiContext<'_, '_, '_, 'info, TransferChecked<'info>> {
+        let cpi_accounts = TransferChecked {
+            from: self.owner_token_account.clone(),
+            mint: self.mint.clone(),
+            to: self.vault_account.clone(),
+            authority: self.owner.to_account_info(),
         };
         CpiContext::new(self.token_program.clone(), cpi_accounts)
     }
+}
+
+impl<'info> InitializeBatch<'info> {
+    /// Creates the vesting account and vault of one batched grant and funds the vault,
+    /// granting what reaches it after transfer fees
+    fn create_grant(
+        &mut self,
+        program_id: &Pubkey,
+        grant: BatchGrant,
+        accounts: &[AccountInfo<'info>],
+        vault_space: usize,
+    ) -> ProgramResult {
+        validate_name(&grant.name)?;
+        validate_periodic_schedule(
+            grant.start_ts,
+            grant.cliff_ts,
+            grant.duration,
+            grant.upfront_bps,
+            grant.withdraw_period,
+        )?;
+
+        let beneficiary_ata_info = &accounts[0];
+        let vesting_info = &accounts[1];
+        let vault_info = &accounts[2];
+
+        let beneficiary_ata = token_account(beneficiary_ata_info)?;
+        if beneficiary_ata.mint != self.mint.key() {
+            return Err(LinearVestingError::InvalidMint.into());
+        }
+        let beneficiary = beneficiary_ata.owner;
+
+        // same addresses `initialize` derives for its `init` accounts
+        let investor_key = self.investor_account.key();
+        let grant_id = self.investor_account.grant_count;
+        let grant_id_bytes = grant_id.to_le_bytes();
+        let (vesting_key, vesting_bump) =
+            find_vesting_account(&investor_key, beneficiary_ata_info.key, grant_id, program_id);
+        let (vault_key, vault_bump) = find_vault_account(&vesting_key, program_id);
+        if vesting_key != *vesting_info.key {
+            return Err(LinearVestingError::InvalidBatch.into());
+        }
+        if vault_key != *vault_info.key {
+            return Err(LinearVestingError::InvalidVault.into());
+        }
+
+        create_pda_account(
+            &self.owner.to_account_info(),
+            vesting_info,
+            VESTING_ACCOUNT_SPACE,
+            program_id,
+            &self.system_program,
+            &self.rent,
+            &[investor_key.as_ref(), beneficiary_ata_info.key.as_ref(), grant_id_bytes.as_ref(), &[vesting_bump]],
+        )?;
+        create_vault(
+            &self.owner.to_account_info(),
+            vault_info,
+            vault_space,
+            &vesting_key,
+            vault_bump,
+            &self.mint,
+            &self.vault_authority,
+            &self.system_program,
+            &self.rent,
+            &self.token_program,
+        )?;
+
+        let decimals = token_interface::mint(&self.mint)?.decimals;
+        token_interface::transfer_checked(
+            CpiContext::new(
+                self.token_program.clone(),
+                TransferChecked {
+                    from: self.owner_token_account.clone(),
+                    mint: self.mint.clone(),
+                    to: vault_info.clone(),
+                    authority: self.owner.to_account_info(),
+                },
+            ),
+            grant.amount,
+            decimals,
+        )?;
+        let received = token_account(vault_info)?.amount;
+        if received == 0 {
+            return Err(LinearVestingError::NoTokens.into());
+        }
 
-    fn into_set_authority_context(&self) -> CpiContext<'_, '_, '_, 'info, SetAuthority<'info>> {
-        let cpi_accounts = SetAuthority {
-            account_or_mint: self.vault_account.to_account_info().clone(),
-            current_authority: self.owner.clone(),
+        let mut vesting_account = VestingAccount {
+            beneficiary,
+            beneficiary_ata: *beneficiary_ata_info.key,
+            investor_account: investor_key,
+            grant_id,
+            revocable: grant.revocable,
+            owner: *self.owner.key,
+            name: grant.name,
+            mint: self.mint.key(),
+            status: VestingStatus::Active,
+            ..Default::default()
         };
-        CpiContext::new(self.token_program.clone(), cpi_accounts)
+        vesting_account.record_deposit(vesting_key, received, Clock::get()?.unix_timestamp)?;
+        vesting_account.set_periodic_schedule(
+            grant.start_ts,
+            grant.cliff_ts,
+            grant.duration,
+            grant.upfront_bps,
+            grant.withdraw_period,
+        );
+        vesting_account.try_serialize(&mut &mut vesting_info.try_borrow_mut_data()?[..])?;
+
+        // register an investor to list
+        self.investor_account.add_grant(beneficiary, vesting_key)?;
+
+        Ok(())
     }
 }
 
 impl<'info> AddTokenToVestingAccount<'info> {
-    fn into_transfer_to_pda_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
-        let cpi_accounts = Transfer {
-            from: self.owner_token_account.to_account_info().clone(),
-            to: self.vault_account.to_account_info().clone(),
-            authority: self.owner.clone(),
+    fn into_transfer_to_pda_context(&self) -> CpiContext<'_, '_, '_, 'info, TransferChecked<'info>> {
+        let cpi_accounts = TransferChecked {
+            from: self.owner_token_account.clone(),
+            mint: self.mint.clone(),
+            to: self.vault_account.clone(),
+            authority: self.owner.to_account_info(),
         };
         CpiContext::new(self.token_program.clone(), cpi_accounts)
     }
 }
 
 impl<'info> Upfront<'info> {
-    fn into_transfer_to_owner_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
-        let cpi_accounts = Transfer {
-            from: self.vault_account.to_account_info().clone(),
-            to: self.owner_token_account.to_account_info().clone(),
+    fn into_transfer_to_beneficiary_context(&self) -> CpiContext<'_, '_, '_, 'info, TransferChecked<'info>> {
+        let cpi_accounts = TransferChecked {
+            from: self.vault_account.clone(),
+            mint: self.mint.clone(),
+            to: self.beneficiary_ata.clone(),
             authority: self.vault_authority.clone(),
         };
         CpiContext::new(self.token_program.clone(), cpi_accounts)
@@ -513,10 +2093,11 @@ impl<'info> Upfront<'info> {
 }
 
 impl<'info> Withdraw<'info> {
-    fn into_transfer_to_owner_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
-        let cpi_accounts = Transfer {
-            from: self.vault_account.to_account_info().clone(),
-            to: self.owner_token_account.to_account_info().clone(),
+    fn into_transfer_to_beneficiary_context(&self) -> CpiContext<'_, '_, '_, 'info, TransferChecked<'info>> {
+        let cpi_accounts = TransferChecked {
+            from: self.vault_account.clone(),
+            mint: self.mint.clone(),
+            to: self.beneficiary_ata.clone(),
             authority: self.vault_authority.clone(),
         };
         CpiContext::new(self.token_program.clone(), cpi_accounts)
@@ -524,12 +2105,52 @@ impl<'info> Withdraw<'info> {
 }
 
 impl<'info> Revoke<'info> {
-    fn into_transfer_to_owner_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
-        let cpi_accounts = Transfer {
-            from: self.vault_account.to_account_info().clone(),
-            to: self.owner_token_account.to_account_info().clone(),
+    fn into_transfer_to_beneficiary_context(&self) -> CpiContext<'_, '_, '_, 'info, TransferChecked<'info>> {
+        let cpi_accounts = TransferChecked {
+            from: self.vault_account.clone(),
+            mint: self.mint.clone(),
+            to: self.beneficiary_ata.clone(),
             authority: self.vault_authority.clone(),
         };
         CpiContext::new(self.token_program.clone(), cpi_accounts)
     }
-}
\ No newline at end of file
+
+    fn into_transfer_to_owner_context(&self) -> CpiContext<'_, '_, '_, 'info, TransferChecked<'info>> {
+        let cpi_accounts = TransferChecked {
+            from: self.vault_account.clone(),
+            mint: self.mint.clone(),
+            to: self.owner_token_account.clone(),
+            authority: self.vault_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+}
+
+impl<'info> StakeVesting<'info> {
+    fn locked_stake(&self) -> staking::LockedStake<'info> {
+        staking::LockedStake {
+            pool: self.staking_pool.clone(),
+            staking_vault: self.staking_vault.clone(),
+            reward_vault: self.staking_reward_vault.clone(),
+            staking_mint: self.mint.clone(),
+            user: self.staking_user.clone(),
+            owner: self.beneficiary.clone(),
+            lock_authority: self.vault_authority.clone(),
+            locked_token_account: self.vault_account.clone(),
+            pool_signer: self.staking_pool_signer.clone(),
+            token_program: self.token_program.clone(),
+            staking_program: self.staking_program.clone(),
+        }
+    }
+}
+
+impl<'info> CloseVesting<'info> {
+    fn into_close_vault_context(&self) -> CpiContext<'_, '_, '_, 'info, CloseAccount<'info>> {
+        let cpi_accounts = CloseAccount {
+            account: self.vault_account.clone(),
+            destination: self.owner.to_account_info(),
+            authority: self.vault_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+}
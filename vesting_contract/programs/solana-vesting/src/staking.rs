@@ -0,0 +1,88 @@
+//! Locked positions in the staking program. The two programs are built with different Anchor
+//! versions, so its instructions are encoded here rather than through its `cpi` module.
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::program::invoke_signed;
+
+pub mod staking_program {
+    use anchor_lang::declare_id;
+
+    declare_id!("6RwUKAHuSbadG6sQzcfEYKh6UGvPvCXB1nq7BPEyn5Jg");
+}
+
+/// `sha256("global:stake_locked")[..8]`
+const STAKE_LOCKED_DISCRIMINATOR: [u8; 8] = [165, 31, 139, 41, 64, 105, 46, 13];
+/// `sha256("global:unstake_locked")[..8]`
+const UNSTAKE_LOCKED_DISCRIMINATOR: [u8; 8] = [251, 104, 108, 47, 168, 90, 90, 199];
+
+/// The accounts of the staking program's `StakeLocked` context
+pub struct LockedStake<'info> {
+    pub pool: AccountInfo<'info>,
+    pub staking_vault: AccountInfo<'info>,
+    pub reward_vault: AccountInfo<'info>,
+    pub staking_mint: AccountInfo<'info>,
+    pub user: AccountInfo<'info>,
+    pub owner: AccountInfo<'info>,
+    pub lock_authority: AccountInfo<'info>,
+    pub locked_token_account: AccountInfo<'info>,
+    pub pool_signer: AccountInfo<'info>,
+    pub token_program: AccountInfo<'info>,
+    pub staking_program: AccountInfo<'info>,
+}
+
+/// Stakes `amount` from the locked token account for the user, signed by the lock authority
+pub fn stake_locked(accounts: &LockedStake, amount: u64, signer_seeds: &[&[&[u8]]]) -> ProgramResult {
+    invoke_locked(STAKE_LOCKED_DISCRIMINATOR, accounts, amount, signer_seeds)
+}
+
+/// Returns `amount` of the locked stake to the locked token account
+pub fn unstake_locked(accounts: &LockedStake, amount: u64, signer_seeds: &[&[&[u8]]]) -> ProgramResult {
+    invoke_locked(UNSTAKE_LOCKED_DISCRIMINATOR, accounts, amount, signer_seeds)
+}
+
+fn invoke_locked(
+    discriminator: [u8; 8],
+    accounts: &LockedStake,
+    amount: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    let mut data = discriminator.to_vec();
+    data.extend_from_slice(&amount.to_le_bytes());
+
+    let instruction = Instruction {
+        program_id: staking_program::ID,
+        accounts: vec![
+            AccountMeta::new(*accounts.pool.key, false),
+            AccountMeta::new(*accounts.staking_vault.key, false),
+            AccountMeta::new_readonly(*accounts.reward_vault.key, false),
+            AccountMeta::new_readonly(*accounts.staking_mint.key, false),
+            AccountMeta::new(*accounts.user.key, false),
+            // the beneficiary signs a stake; the owner unstakes without them
+            AccountMeta::new_readonly(*accounts.owner.key, accounts.owner.is_signer),
+            AccountMeta::new_readonly(*accounts.lock_authority.key, true),
+            AccountMeta::new(*accounts.locked_token_account.key, false),
+            AccountMeta::new_readonly(*accounts.pool_signer.key, false),
+            AccountMeta::new_readonly(*accounts.token_program.key, false),
+        ],
+        data,
+    };
+
+    invoke_signed(
+        &instruction,
+        &[
+            accounts.pool.clone(),
+            accounts.staking_vault.clone(),
+            accounts.reward_vault.clone(),
+            accounts.staking_mint.clone(),
+            accounts.user.clone(),
+            accounts.owner.clone(),
+            accounts.lock_authority.clone(),
+            accounts.locked_token_account.clone(),
+            accounts.pool_signer.clone(),
+            accounts.token_program.clone(),
+            accounts.staking_program.clone(),
+        ],
+        signer_seeds,
+    )
+}
@@ -0,0 +1,65 @@
+//! Grant vaults, payout accounts and mints of either token program. Parsing and the token CPIs
+//! live in `token_common`, which the staking program shares; this module takes them to Anchor
+//! 0.20's `CpiContext`s, whose token types only accept SPL Token accounts.
+
+use anchor_lang::prelude::*;
+use anchor_spl::token::{CloseAccount, InitializeAccount};
+
+pub use token_common::{
+    account_space, harvest_withheld_tokens_to_mint, is_supported_mint, is_token_program, mint,
+    token_2022, token_account,
+};
+
+#[derive(Accounts)]
+pub struct TransferChecked<'info> {
+    pub from: AccountInfo<'info>,
+    pub mint: AccountInfo<'info>,
+    pub to: AccountInfo<'info>,
+    pub authority: AccountInfo<'info>,
+}
+
+/// Moves `amount` tokens of a mint with `decimals` decimals. With a Token-2022 transfer fee the
+/// destination is credited less than `amount`.
+pub fn transfer_checked<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, TransferChecked<'info>>,
+    amount: u64,
+    decimals: u8,
+) -> ProgramResult {
+    token_common::transfer_checked(
+        ctx.program,
+        token_common::TransferChecked {
+            from: ctx.accounts.from,
+            mint: ctx.accounts.mint,
+            to: ctx.accounts.to,
+            authority: ctx.accounts.authority,
+        },
+        amount,
+        decimals,
+        ctx.signer_seeds,
+    )
+}
+
+pub fn initialize_account<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, InitializeAccount<'info>>,
+) -> ProgramResult {
+    token_common::initialize_account(
+        ctx.program,
+        ctx.accounts.account,
+        ctx.accounts.mint,
+        ctx.accounts.authority,
+        ctx.accounts.rent,
+        ctx.signer_seeds,
+    )
+}
+
+pub fn close_account<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, CloseAccount<'info>>,
+) -> ProgramResult {
+    token_common::close_account(
+        ctx.program,
+        ctx.accounts.account,
+        ctx.accounts.destination,
+        ctx.accounts.authority,
+        ctx.signer_seeds,
+    )
+}
@@ -10,6 +10,12 @@ import {
 } from '@solana/web3.js';
 import { TOKEN_PROGRAM_ID, Token } from '@solana/spl-token';
 import { assert } from 'chai';
+import fs from 'fs';
+
+const TOKEN_2022_PROGRAM_ID = new PublicKey('TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb');
+// loaded into the test validator from the staking workspace, see Anchor.toml
+const STAKING_PROGRAM_ID = new PublicKey('6RwUKAHuSbadG6sQzcfEYKh6UGvPvCXB1nq7BPEyn5Jg');
+const STAKING_IDL_PATH = '../staking_contract/target/idl/staking_contract.json';
 
 describe('solana-staking', () => {
   const provider = anchor.Provider.env();
@@ -30,26 +36,39 @@ describe('solana-staking', () => {
   let vestingAccount = null;
   let vaultAuthority = null;
   let investorAccount = null;
+  let milestoneBeneficiary = null;
+  let milestoneVesting = null;
+  let milestoneVault = null;
 
   it('Initialize vesting contract', async () => {
-    [investorAccount] = await PublicKey.findProgramAddress(
-      [Buffer.from(anchor.utils.bytes.utf8.encode('investor-account'))],
-      program.programId
+    mint = await Token.createMint(
+      provider.connection,
+      mintAuthority,
+      mintAuthority.publicKey,
+      null,
+      9,
+      TOKEN_PROGRAM_ID
     );
 
+    investorAccount = await findInvestorAccount(program, owner.publicKey, mint.publicKey);
+
     await program.rpc.initializeVesting(
       {
         accounts: {
           owner: owner.publicKey,
           investorAccount,
+          mint: mint.publicKey,
           systemProgram: anchor.web3.SystemProgram.programId,
         },
       }
     );
+
+    let _investorAccount = await program.account.investorAccount.fetch(investorAccount);
+    assert.ok(_investorAccount.mint.equals(mint.publicKey));
   })
 
-  it('Initialize vesting account', async () => {
-    mint = await Token.createMint(
+  it('Keeps a separate investor account per project', async () => {
+    const otherMint = await Token.createMint(
       provider.connection,
       mintAuthority,
       mintAuthority.publicKey,
@@ -57,7 +76,30 @@ describe('solana-staking', () => {
       9,
       TOKEN_PROGRAM_ID
     );
+    const otherInvestorAccount = await findInvestorAccount(
+      program,
+      owner.publicKey,
+      otherMint.publicKey
+    );
+    assert.ok(!otherInvestorAccount.equals(investorAccount));
+
+    await program.rpc.initializeVesting(
+      {
+        accounts: {
+          owner: owner.publicKey,
+          investorAccount: otherInvestorAccount,
+          mint: otherMint.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        },
+      }
+    );
 
+    let _otherInvestorAccount = await program.account.investorAccount.fetch(otherInvestorAccount);
+    assert.ok(_otherInvestorAccount.mint.equals(otherMint.publicKey));
+    assert.ok(_otherInvestorAccount.grantCount.toNumber() === 0);
+  })
+
+  it('Initialize vesting account', async () => {
     ownerTokenAccount = await mint.getOrCreateAssociatedAccountInfo(
       owner.publicKey
     );
@@ -70,40 +112,41 @@ describe('solana-staking', () => {
     );
 
     beneficiaryTokenAccount = await mint.getOrCreateAssociatedAccountInfo(
-      owner.publicKey
+      beneficiary.publicKey
     );
 
-    [vaultAccount] = await PublicKey.findProgramAddress(
-      [
-        Buffer.from(anchor.utils.bytes.utf8.encode('token-vault')),
-        beneficiaryTokenAccount.address.toBuffer(),
-      ],
-
-      program.programId
-    );
 
     [vaultAuthority] = await PublicKey.findProgramAddress(
-      [Buffer.from(anchor.utils.bytes.utf8.encode('vault-authority'))],
-      program.programId
-    );
-
-    [vestingAccount] = await PublicKey.findProgramAddress(
-      [beneficiaryTokenAccount.address.toBuffer()],
+      [
+        Buffer.from(anchor.utils.bytes.utf8.encode('vault-authority')),
+        investorAccount.toBuffer(),
+      ],
       program.programId
     );
 
-    [investorAccount] = await PublicKey.findProgramAddress(
-      [Buffer.from(anchor.utils.bytes.utf8.encode('investor-account'))],
-      program.programId
+    [vestingAccount, vaultAccount] = await findGrantAddresses(
+      program,
+      investorAccount,
+      beneficiaryTokenAccount.address
     );
+    // lamports sent to the vault's address beforehand do not block the grant
+    await fundAddress(provider, vaultAccount, 1_000_000);
 
     const startTs = new anchor.BN(Date.now() / 1000);
+    const cliffTs = startTs;
+    const duration = new anchor.BN(30);
+    const upfrontBps = new anchor.BN(1000);
+    const withdrawPeriod = new anchor.BN(1);
     const name = "Investor";
 
     await program.rpc.initialize(
       new anchor.BN(amount / 2),
       name,
       startTs,
+      cliffTs,
+      duration,
+      upfrontBps,
+      withdrawPeriod,
       true,
       {
         accounts: {
@@ -112,6 +155,7 @@ describe('solana-staking', () => {
           mint: mint.publicKey,
           beneficiaryAta: beneficiaryTokenAccount.address,
           vaultAccount: vaultAccount,
+          vaultAuthority: vaultAuthority,
           ownerTokenAccount: ownerTokenAccount.address,
           vestingAccount: vestingAccount,
           investorAccount: investorAccount,
@@ -143,19 +187,325 @@ describe('solana-staking', () => {
     assert.ok(_vestingAccount.totalDepositedAmount.toNumber() === amount / 2);
     assert.ok(_vestingAccount.releasedAmount.toNumber() === 0);
     assert.ok(_vestingAccount.startTs.eq(startTs));
+    assert.ok(_vestingAccount.cliffTs.eq(cliffTs));
+    assert.ok(_vestingAccount.duration.eq(duration));
+    assert.ok(_vestingAccount.upfrontBps.eq(upfrontBps));
+    assert.ok(_vestingAccount.withdrawPeriod.eq(withdrawPeriod));
     assert.ok(_vestingAccount.revocable);
     assert.ok(_vestingAccount.beneficiary.equals(beneficiary.publicKey));
     assert.ok(_vestingAccount.owner.equals(owner.publicKey));
     assert.ok(_vestingAccount.mint.equals(mint.publicKey));
   });
 
-  it('disable investor account', async () => {
-    [investorAccount] = await PublicKey.findProgramAddress(
-      [Buffer.from(anchor.utils.bytes.utf8.encode('investor-account'))],
-      program.programId
+  it('Initialize and withdraw a milestone vesting account', async () => {
+    milestoneBeneficiary = anchor.web3.Keypair.generate();
+    const milestoneBeneficiaryAta = await mint.createAssociatedTokenAccount(
+      milestoneBeneficiary.publicKey
+    );
+
+    [milestoneVesting, milestoneVault] = await findGrantAddresses(
+      program,
+      investorAccount,
+      milestoneBeneficiaryAta
+    );
+
+    const now = Math.floor(Date.now() / 1000);
+    const tranches = [
+      { unlockTs: new anchor.BN(now - 10), amount: new anchor.BN(1000) },
+      { unlockTs: new anchor.BN(now + 3600), amount: new anchor.BN(3000) },
+    ];
+
+    await program.rpc.initializeMilestone(
+      new anchor.BN(4000),
+      "Milestone Investor",
+      tranches,
+      true,
+      {
+        accounts: {
+          owner: owner.publicKey,
+          beneficiary: milestoneBeneficiary.publicKey,
+          mint: mint.publicKey,
+          beneficiaryAta: milestoneBeneficiaryAta,
+          vaultAccount: milestoneVault,
+          vaultAuthority: vaultAuthority,
+          ownerTokenAccount: ownerTokenAccount.address,
+          vestingAccount: milestoneVesting,
+          investorAccount: investorAccount,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        },
+      }
+    );
+
+    await program.rpc.withdraw(
+      {
+        accounts: {
+          beneficiary: milestoneBeneficiary.publicKey,
+          beneficiaryAta: milestoneBeneficiaryAta,
+          vaultAccount: milestoneVault,
+          vestingAccount: milestoneVesting,
+          mint: mint.publicKey,
+          vaultAuthority: vaultAuthority,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        },
+        signers: [milestoneBeneficiary],
+      }
     );
 
+    let _vestingAccount = await program.account.vestingAccount.fetch(
+      milestoneVesting
+    );
+    // only the tranche that already unlocked is released
+    assert.ok(_vestingAccount.releasedAmount.toNumber() === 1000);
+
+    // the release time is the cluster's clock, so the next tranche stays locked
+    await assertRejected(program.rpc.withdraw(
+      {
+        accounts: {
+          beneficiary: milestoneBeneficiary.publicKey,
+          beneficiaryAta: milestoneBeneficiaryAta,
+          vaultAccount: milestoneVault,
+          vestingAccount: milestoneVesting,
+          mint: mint.publicKey,
+          vaultAuthority: vaultAuthority,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        },
+        signers: [milestoneBeneficiary],
+      }
+    ));
+    _vestingAccount = await program.account.vestingAccount.fetch(milestoneVesting);
+    assert.ok(_vestingAccount.releasedAmount.toNumber() === 1000);
+
+    let _investorAccount = await program.account.investorAccount.fetch(investorAccount);
+    assert.ok(_investorAccount.grantCount.toNumber() === 2);
+    assert.ok(_investorAccount.grants[1].equals(milestoneVesting));
+  });
+
+  it('Transfers a grant to a new beneficiary', async () => {
+    const newBeneficiary = anchor.web3.Keypair.generate();
+    const newBeneficiaryAta = await mint.createAssociatedTokenAccount(
+      newBeneficiary.publicKey
+    );
+
+    await program.rpc.setTransferApproval(
+      true,
+      {
+        accounts: {
+          investorAccount,
+          vestingAccount: milestoneVesting,
+          owner: owner.publicKey,
+        },
+      }
+    );
+
+    // the owner co-signs as the fee payer
+    await program.rpc.transferBeneficiary(
+      {
+        accounts: {
+          beneficiary: milestoneBeneficiary.publicKey,
+          newBeneficiary: newBeneficiary.publicKey,
+          newBeneficiaryAta,
+          investorAccount,
+          vestingAccount: milestoneVesting,
+          owner: owner.publicKey,
+        },
+        signers: [milestoneBeneficiary],
+      }
+    );
+
+    let _vestingAccount = await program.account.vestingAccount.fetch(
+      milestoneVesting
+    );
+    assert.ok(_vestingAccount.beneficiary.equals(newBeneficiary.publicKey));
+    assert.ok(_vestingAccount.beneficiaryAta.equals(newBeneficiaryAta));
+    assert.ok(_vestingAccount.releasedAmount.toNumber() === 1000);
+
+    let _investorAccount = await program.account.investorAccount.fetch(investorAccount);
+    assert.ok(_investorAccount.investors[1].equals(newBeneficiary.publicKey));
+
+    // the previous beneficiary no longer controls the grant
+    await assertRejected(program.rpc.transferBeneficiary(
+      {
+        accounts: {
+          beneficiary: milestoneBeneficiary.publicKey,
+          newBeneficiary: owner.publicKey,
+          newBeneficiaryAta: ownerTokenAccount.address,
+          investorAccount,
+          vestingAccount: milestoneVesting,
+          owner: owner.publicKey,
+        },
+        signers: [milestoneBeneficiary],
+      }
+    ));
+  });
+
+  it('Creates several grants in one batch', async () => {
+    const _investorAccount = await program.account.investorAccount.fetch(investorAccount);
+    const firstGrantId = _investorAccount.grantCount.toNumber();
+    const startTs = new anchor.BN(Math.floor(Date.now() / 1000));
+
+    const grants = [];
+    const remainingAccounts = [];
+    for (let i = 0; i < 2; i++) {
+      const batchBeneficiary = anchor.web3.Keypair.generate();
+      const batchBeneficiaryAta = await mint.createAssociatedTokenAccount(
+        batchBeneficiary.publicKey
+      );
+      const [batchVesting] = await PublicKey.findProgramAddress(
+        [
+          investorAccount.toBuffer(),
+          batchBeneficiaryAta.toBuffer(),
+          new anchor.BN(firstGrantId + i).toArrayLike(Buffer, 'le', 8),
+        ],
+        program.programId
+      );
+      const [batchVault] = await PublicKey.findProgramAddress(
+        [
+          Buffer.from(anchor.utils.bytes.utf8.encode('token-vault')),
+          batchVesting.toBuffer(),
+        ],
+        program.programId
+      );
+
+      grants.push({
+        amount: new anchor.BN(1000 * (i + 1)),
+        name: `Batch Investor ${i}`,
+        startTs,
+        cliffTs: startTs,
+        duration: new anchor.BN(60),
+        upfrontBps: new anchor.BN(500),
+        withdrawPeriod: new anchor.BN(10),
+        revocable: true,
+      });
+      if (i === 0) {
+        // anyone can send lamports to an address before the grant is created there
+        await fundAddress(provider, batchVesting, 1_000_000);
+        await fundAddress(provider, batchVault, 1_000_000);
+      }
+      remainingAccounts.push(
+        { pubkey: batchBeneficiaryAta, isWritable: false, isSigner: false },
+        { pubkey: batchVesting, isWritable: true, isSigner: false },
+        { pubkey: batchVault, isWritable: true, isSigner: false },
+      );
+    }
+
+    await program.rpc.initializeBatch(
+      grants,
+      {
+        accounts: {
+          owner: owner.publicKey,
+          mint: mint.publicKey,
+          ownerTokenAccount: ownerTokenAccount.address,
+          investorAccount,
+          vaultAuthority,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        },
+        remainingAccounts,
+      }
+    );
+
+    const _investorAfter = await program.account.investorAccount.fetch(investorAccount);
+    assert.ok(_investorAfter.grantCount.toNumber() === firstGrantId + 2);
+
+    for (let i = 0; i < 2; i++) {
+      const batchVesting = remainingAccounts[3 * i + 1].pubkey;
+      const batchVault = remainingAccounts[3 * i + 2].pubkey;
+      const _batchVesting = await program.account.vestingAccount.fetch(batchVesting);
+      const _batchVault = await mint.getAccountInfo(batchVault);
+
+      assert.ok(_investorAfter.grants[firstGrantId + i].equals(batchVesting));
+      assert.ok(_batchVesting.grantId.toNumber() === firstGrantId + i);
+      assert.ok(_batchVesting.name === grants[i].name);
+      assert.ok(_batchVesting.totalDepositedAmount.eq(grants[i].amount));
+      assert.ok(_batchVault.amount.eq(grants[i].amount));
+      assert.ok(_batchVault.owner.equals(vaultAuthority));
+    }
+  });
+
+  it('Shifts the schedule by the paused time', async () => {
+    const accounts = {
+      owner: owner.publicKey,
+      investorAccount: investorAccount,
+      vestingAccount: milestoneVesting,
+      systemProgram: anchor.web3.SystemProgram.programId,
+    };
+    const before = await program.account.vestingAccount.fetch(milestoneVesting);
+
+    await program.rpc.disableAccount(true, { accounts });
+    await delay(3000);
+    await program.rpc.enableAccount({ accounts });
+
+    const after = await program.account.vestingAccount.fetch(milestoneVesting);
+    const shift = after.tranches[1].unlockTs.sub(before.tranches[1].unlockTs).toNumber();
+    assert.ok(after.status.active);
+    assert.ok(shift > 0);
+    assert.ok(after.startTs.sub(before.startTs).toNumber() === shift);
+    // tokens already released are unaffected
+    assert.ok(after.releasedAmount.eq(before.releasedAmount));
+  });
+
+  it('Tops up a milestone grant after a claim', async () => {
+    const accounts = {
+      owner: owner.publicKey,
+      vaultAccount: milestoneVault,
+      vestingAccount: milestoneVesting,
+      mint: mint.publicKey,
+      ownerTokenAccount: ownerTokenAccount.address,
+      systemProgram: anchor.web3.SystemProgram.programId,
+      rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    };
+    const before = await program.account.vestingAccount.fetch(milestoneVesting);
+
+    // only the tranche still locked takes the pro-rata top-up
+    await program.rpc.addTokenToVesting(new anchor.BN(600), { proRata: {} }, { accounts });
+    let _vestingAccount = await program.account.vestingAccount.fetch(milestoneVesting);
+    assert.ok(_vestingAccount.tranches[0].amount.eq(before.tranches[0].amount));
+    assert.ok(_vestingAccount.tranches[1].amount.toNumber() === before.tranches[1].amount.toNumber() + 600);
+
+    const startTs = before.tranches[1].unlockTs.addn(3600);
+    await program.rpc.addTokenToVesting(new anchor.BN(400), { newTranche: { startTs } }, { accounts });
+    _vestingAccount = await program.account.vestingAccount.fetch(milestoneVesting);
+    assert.ok(_vestingAccount.tranches.length === 3);
+    assert.ok(_vestingAccount.tranches[2].unlockTs.eq(startTs));
+    assert.ok(_vestingAccount.tranches[2].amount.toNumber() === 400);
+    assert.ok(_vestingAccount.totalDepositedAmount.toNumber() === before.totalDepositedAmount.toNumber() + 1000);
+
+    let _vault = await mint.getAccountInfo(milestoneVault);
+    assert.ok(_vault.amount.toNumber() === _vestingAccount.totalDepositedAmount.toNumber() - _vestingAccount.releasedAmount.toNumber());
+
+    // a new tranche cannot unlock at once
+    const nowTs = Math.floor(Date.now() / 1000);
+    await assertRejected(program.rpc.addTokenToVesting(
+      new anchor.BN(10),
+      { newTranche: { startTs: new anchor.BN(nowTs - 60) } },
+      { accounts }
+    ));
+
+    // a grant holds at most 8 tranches
+    for (let i = 1; i <= 5; i++) {
+      const laterTs = startTs.addn(3600 * i);
+      await program.rpc.addTokenToVesting(new anchor.BN(10), { newTranche: { startTs: laterTs } }, { accounts });
+    }
+    _vestingAccount = await program.account.vestingAccount.fetch(milestoneVesting);
+    assert.ok(_vestingAccount.tranches.length === 8);
+    await assertRejected(program.rpc.addTokenToVesting(
+      new anchor.BN(10),
+      { newTranche: { startTs: startTs.addn(3600 * 6) } },
+      { accounts }
+    ));
+  });
+
+  it('disable investor account', async () => {
     await program.rpc.disableAccount(
+      false,
       {
         accounts: {
           owner: owner.publicKey,
@@ -171,16 +521,12 @@ describe('solana-staking', () => {
     );
 
     console.log("===========vestingAccountAfterDisabled=============", _vestingAccount)
+    assert.ok(_vestingAccount.status.paused);
   });
 
   it('enable investor account', async () => {
     await delay(2000);
 
-    [investorAccount] = await PublicKey.findProgramAddress(
-      [Buffer.from(anchor.utils.bytes.utf8.encode('investor-account'))],
-      program.programId
-    );
-
     await program.rpc.enableAccount(
       {
         accounts: {
@@ -197,15 +543,11 @@ describe('solana-staking', () => {
     );
 
     console.log("===========vestingAccountAfterEnabled=============", _vestingAccount)
+    assert.ok(_vestingAccount.status.active);
   });
 
   it('rename account', async () => {
 
-    [investorAccount] = await PublicKey.findProgramAddress(
-      [Buffer.from(anchor.utils.bytes.utf8.encode('investor-account'))],
-      program.programId
-    );
-
     const newName = "New Investor"
     await program.rpc.renameAccount(
       newName,
@@ -226,9 +568,38 @@ describe('solana-staking', () => {
     console.log("===========vestingAccountAfterRenamed=============", _vestingAccount)
   });
 
+  it('Limits names to the space reserved for them', async () => {
+    const renameTo = (name) => program.rpc.renameAccount(
+      name,
+      {
+        accounts: {
+          owner: owner.publicKey,
+          investorAccount: investorAccount,
+          vestingAccount: vestingAccount,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        },
+      }
+    );
+
+    // MAX_NAME_LEN bytes fit, one more does not
+    const longestName = "N".repeat(32);
+    await renameTo(longestName);
+    let _vestingAccount = await program.account.vestingAccount.fetch(vestingAccount);
+    assert.ok(_vestingAccount.name === longestName);
+
+    await assertRejected(renameTo("N".repeat(33)));
+
+    await renameTo("New Investor");
+  });
+
   it('Upfront token', async () => {
     await delay(5000);
 
+    let released = null;
+    const listener = program.addEventListener('TokensReleased', (event) => {
+      released = event;
+    });
+
     await program.rpc.upfront(
       {
         accounts: {
@@ -236,12 +607,13 @@ describe('solana-staking', () => {
           beneficiaryAta: beneficiaryTokenAccount.address,
           vaultAccount: vaultAccount,
           vestingAccount: vestingAccount,
+          mint: mint.publicKey,
           vaultAuthority: vaultAuthority,
-          ownerTokenAccount: ownerTokenAccount.address,
           systemProgram: anchor.web3.SystemProgram.programId,
           rent: anchor.web3.SYSVAR_RENT_PUBKEY,
           tokenProgram: TOKEN_PROGRAM_ID,
         },
+        signers: [beneficiary],
       }
     );
 
@@ -268,33 +640,185 @@ describe('solana-staking', () => {
       mint: _vestingAccount.mint.toBase58(),
       totalDepositedAmount: _vestingAccount.totalDepositedAmount.toNumber(),
       releasedAmount: _vestingAccount.releasedAmount.toNumber(),
-      revoked: _vestingAccount.revoked,
+      status: _vestingAccount.status,
       upfronted: _vestingAccount.upfronted,
     }
 
     console.log("============vestingAccountAfterWithdraw==============", vestingAccountAfterUpfront)
 
+    await delay(1000);
+    await program.removeEventListener(listener);
+    assert.ok(released !== null);
+    assert.ok(released.vestingAccount.equals(vestingAccount));
+    assert.ok(released.kind.upfront);
+    assert.ok(released.amount.toNumber() === _vestingAccount.releasedAmount.toNumber());
+    assert.ok(released.releasedAmount.toNumber() === _vestingAccount.releasedAmount.toNumber());
+    assert.ok(released.claimedCount === 1);
+  });
+
+  it('Withdraws the periods completed before a late upfront', async () => {
+    const lateBeneficiary = anchor.web3.Keypair.generate();
+    const lateBeneficiaryAta = await mint.createAssociatedTokenAccount(
+      lateBeneficiary.publicKey
+    );
+    const [lateVesting, lateVault] = await findGrantAddresses(
+      program,
+      investorAccount,
+      lateBeneficiaryAta
+    );
+
+    // two of ten 10-second periods completed before the upfront is taken
+    const startTs = new anchor.BN(Math.floor(Date.now() / 1000) - 25);
+    await program.rpc.initialize(
+      new anchor.BN(1_000_000),
+      "Late Investor",
+      startTs,
+      startTs,
+      new anchor.BN(100),
+      new anchor.BN(1000),
+      new anchor.BN(10),
+      true,
+      {
+        accounts: {
+          owner: owner.publicKey,
+          beneficiary: lateBeneficiary.publicKey,
+          mint: mint.publicKey,
+          beneficiaryAta: lateBeneficiaryAta,
+          vaultAccount: lateVault,
+          vaultAuthority: vaultAuthority,
+          ownerTokenAccount: ownerTokenAccount.address,
+          vestingAccount: lateVesting,
+          investorAccount: investorAccount,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        },
+      }
+    );
+
+    const accounts = {
+      beneficiary: lateBeneficiary.publicKey,
+      beneficiaryAta: lateBeneficiaryAta,
+      vaultAccount: lateVault,
+      vestingAccount: lateVesting,
+      mint: mint.publicKey,
+      vaultAuthority: vaultAuthority,
+      systemProgram: anchor.web3.SystemProgram.programId,
+      rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    };
+    await program.rpc.upfront({ accounts, signers: [lateBeneficiary] });
+    let _vestingAccount = await program.account.vestingAccount.fetch(lateVesting);
+    assert.ok(_vestingAccount.releasedAmount.toNumber() === 100_000);
+    assert.ok(_vestingAccount.withdrawTs.eq(startTs));
+
+    // the completed periods do not wait for the next period boundary
+    await program.rpc.withdraw({ accounts, signers: [lateBeneficiary] });
+    _vestingAccount = await program.account.vestingAccount.fetch(lateVesting);
+    assert.ok(_vestingAccount.releasedAmount.toNumber() >= 100_000 + 2 * 90_000);
+  });
+
+  it('Rejects withdrawals with forged accounts', async () => {
+    const attacker = anchor.web3.Keypair.generate();
+    const attackerAta = await mint.createAssociatedTokenAccount(attacker.publicKey);
+    const [forgedAuthority] = await PublicKey.findProgramAddress(
+      [Buffer.from(anchor.utils.bytes.utf8.encode('unrelated'))],
+      program.programId
+    );
+    const withdrawWith = (accounts, signers) => program.rpc.withdraw(
+      {
+        accounts: {
+          beneficiary: beneficiary.publicKey,
+          beneficiaryAta: beneficiaryTokenAccount.address,
+          vaultAccount: vaultAccount,
+          vestingAccount: vestingAccount,
+          mint: mint.publicKey,
+          vaultAuthority: vaultAuthority,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          ...accounts,
+        },
+        signers,
+      }
+    );
+
+    // someone other than the beneficiary signs
+    await assertRejected(withdrawWith({ beneficiary: attacker.publicKey }, [attacker]));
+    // the payout goes to a token account the beneficiary doesn't own
+    await assertRejected(withdrawWith({ beneficiaryAta: attackerAta }, [beneficiary]));
+    await assertRejected(
+      withdrawWith({ beneficiaryAta: ownerTokenAccount.address }, [beneficiary])
+    );
+    // the vault doesn't belong to the vesting account
+    await assertRejected(withdrawWith({ vaultAccount: ownerTokenAccount.address }, [beneficiary]));
+    // the vault authority isn't the project's PDA
+    await assertRejected(withdrawWith({ vaultAuthority: forgedAuthority }, [beneficiary]));
+  });
+
+  it('Rejects revoke by anyone but the owner', async () => {
+    const attacker = anchor.web3.Keypair.generate();
+    const attackerAta = await mint.createAssociatedTokenAccount(attacker.publicKey);
+
+    await assertRejected(program.rpc.revoke(
+      {
+        accounts: {
+          owner: attacker.publicKey,
+          vaultAccount: vaultAccount,
+          vestingAccount: vestingAccount,
+          mint: mint.publicKey,
+          ownerTokenAccount: attackerAta,
+          beneficiaryAta: beneficiaryTokenAccount.address,
+          vaultAuthority: vaultAuthority,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        },
+        signers: [attacker],
+      }
+    ));
+
+    // the owner signs, but the refund is sent elsewhere
+    await assertRejected(program.rpc.revoke(
+      {
+        accounts: {
+          owner: owner.publicKey,
+          vaultAccount: vaultAccount,
+          vestingAccount: vestingAccount,
+          mint: mint.publicKey,
+          ownerTokenAccount: attackerAta,
+          beneficiaryAta: beneficiaryTokenAccount.address,
+          vaultAuthority: vaultAuthority,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        },
+      }
+    ));
+
+    let _vestingAccount = await program.account.vestingAccount.fetch(
+      vestingAccount
+    );
+    assert.ok(!_vestingAccount.status.revoked);
   });
 
   it('Withdraw token', async () => {
     await delay(5000);
 
-    const withdrawTs = new anchor.BN(Date.now() / 1000);
-
     await program.rpc.withdraw(
-      withdrawTs,
       {
         accounts: {
           beneficiary: beneficiary.publicKey,
           beneficiaryAta: beneficiaryTokenAccount.address,
           vaultAccount: vaultAccount,
           vestingAccount: vestingAccount,
+          mint: mint.publicKey,
           vaultAuthority: vaultAuthority,
-          ownerTokenAccount: ownerTokenAccount.address,
           systemProgram: anchor.web3.SystemProgram.programId,
           rent: anchor.web3.SYSVAR_RENT_PUBKEY,
           tokenProgram: TOKEN_PROGRAM_ID,
         },
+        signers: [beneficiary],
       }
     );
 
@@ -321,28 +845,38 @@ describe('solana-staking', () => {
       mint: _vestingAccount.mint.toBase58(),
       totalDepositedAmount: _vestingAccount.totalDepositedAmount.toNumber(),
       releasedAmount: _vestingAccount.releasedAmount.toNumber(),
-      revoked: _vestingAccount.revoked,
+      status: _vestingAccount.status,
       upfronted: _vestingAccount.upfronted,
     }
 
     console.log("============vestingAccountAfterWithdraw==============", vestingAccountAfterWithdraw)
 
+    // every period elapsed since the upfront is paid out in one withdraw
+    const total = amount / 2;
+    const upfront = total / 10;
+    const periodSlice = (total - upfront) / 30;
+    assert.ok(vestingAccountAfterWithdraw.releasedAmount - upfront >= 2 * periodSlice);
   });
 
   it('Revoke', async () => {
+    const beneficiaryBefore = await mint.getAccountInfo(beneficiaryTokenAccount.address);
+
+    await delay(2000);
+
     await program.rpc.revoke(
       {
         accounts: {
           owner: owner.publicKey,
           vaultAccount: vaultAccount,
           vestingAccount: vestingAccount,
+          mint: mint.publicKey,
           ownerTokenAccount: ownerTokenAccount.address,
+          beneficiaryAta: beneficiaryTokenAccount.address,
           vaultAuthority: vaultAuthority,
           systemProgram: anchor.web3.SystemProgram.programId,
           rent: anchor.web3.SYSVAR_RENT_PUBKEY,
           tokenProgram: TOKEN_PROGRAM_ID,
         },
-        // signers: [owner],
       }
     );
 
@@ -362,11 +896,708 @@ describe('solana-staking', () => {
     let _vestingAccount = await program.account.vestingAccount.fetch(
       vestingAccount
     );
-    assert.ok(_vestingAccount.revoked);
+    assert.ok(_vestingAccount.status.revoked);
+
+    // the beneficiary was paid what vested before the revocation, the owner got the rest
+    const paidOnRevoke = _beneficiary.amount.sub(beneficiaryBefore.amount).toNumber();
+    assert.ok(paidOnRevoke > 0);
+    assert.ok(_vestingAccount.releasedAmount.toNumber() === _beneficiary.amount.toNumber());
+    assert.ok(_vestingAccount.totalDepositedAmount.eq(_vestingAccount.releasedAmount));
+    assert.ok(_vestingAccount.revokedTs.toNumber() > 0);
+
+    // a revoked grant can never be resumed
+    await assertRejected(program.rpc.enableAccount(
+      {
+        accounts: {
+          owner: owner.publicKey,
+          investorAccount: investorAccount,
+          vestingAccount: vestingAccount,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        },
+      }
+    ));
+
+    // nor moved to another beneficiary
+    await assertRejected(program.rpc.transferBeneficiary(
+      {
+        accounts: {
+          beneficiary: beneficiary.publicKey,
+          newBeneficiary: owner.publicKey,
+          newBeneficiaryAta: ownerTokenAccount.address,
+          investorAccount,
+          vestingAccount,
+          owner: owner.publicKey,
+        },
+        signers: [beneficiary],
+      }
+    ));
+  });
+
+  it('Closes a revoked grant and reclaims its rent', async () => {
+    const ownerLamportsBefore = await provider.connection.getBalance(owner.publicKey);
+
+    await program.rpc.closeVesting(
+      {
+        accounts: {
+          owner: owner.publicKey,
+          investorAccount: investorAccount,
+          vestingAccount: vestingAccount,
+          vaultAccount: vaultAccount,
+          mint: mint.publicKey,
+          vaultAuthority: vaultAuthority,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        },
+      }
+    );
+
+    assert.ok((await provider.connection.getAccountInfo(vestingAccount)) === null);
+    assert.ok((await provider.connection.getAccountInfo(vaultAccount)) === null);
+    assert.ok((await provider.connection.getBalance(owner.publicKey)) > ownerLamportsBefore);
 
+    let _investorAccount = await program.account.investorAccount.fetch(investorAccount);
+    assert.ok(!_investorAccount.grants.some((grant) => grant.equals(vestingAccount)));
+    assert.ok(_investorAccount.grants.length === _investorAccount.investors.length);
+  });
+
+  it('Refuses to close a grant that is still vesting', async () => {
+    await assertRejected(program.rpc.closeVesting(
+      {
+        accounts: {
+          owner: owner.publicKey,
+          investorAccount: investorAccount,
+          vestingAccount: milestoneVesting,
+          vaultAccount: milestoneVault,
+          mint: mint.publicKey,
+          vaultAuthority: vaultAuthority,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        },
+      }
+    ));
+  });
+
+  it('Does not vest the paused time of a grant revoked while paused', async () => {
+    const pausedBeneficiary = anchor.web3.Keypair.generate();
+    const pausedBeneficiaryAta = await mint.createAssociatedTokenAccount(
+      pausedBeneficiary.publicKey
+    );
+    const [pausedVesting, pausedVault] = await findGrantAddresses(
+      program,
+      investorAccount,
+      pausedBeneficiaryAta
+    );
+
+    const now = Math.floor(Date.now() / 1000);
+    const tranches = [
+      { unlockTs: new anchor.BN(now + 3), amount: new anchor.BN(1000) },
+    ];
+
+    await program.rpc.initializeMilestone(
+      new anchor.BN(1000),
+      "Paused Investor",
+      tranches,
+      true,
+      {
+        accounts: {
+          owner: owner.publicKey,
+          beneficiary: pausedBeneficiary.publicKey,
+          mint: mint.publicKey,
+          beneficiaryAta: pausedBeneficiaryAta,
+          vaultAccount: pausedVault,
+          vaultAuthority: vaultAuthority,
+          ownerTokenAccount: ownerTokenAccount.address,
+          vestingAccount: pausedVesting,
+          investorAccount: investorAccount,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        },
+      }
+    );
+
+    await program.rpc.disableAccount(true, {
+      accounts: {
+        owner: owner.publicKey,
+        investorAccount: investorAccount,
+        vestingAccount: pausedVesting,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      },
+    });
+    // the tranche's unlock time passes while the grant is paused
+    await delay(5000);
+
+    await program.rpc.revoke(
+      {
+        accounts: {
+          owner: owner.publicKey,
+          vaultAccount: pausedVault,
+          vestingAccount: pausedVesting,
+          mint: mint.publicKey,
+          ownerTokenAccount: ownerTokenAccount.address,
+          beneficiaryAta: pausedBeneficiaryAta,
+          vaultAuthority: vaultAuthority,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        },
+      }
+    );
+
+    // the pause shifted the tranche past the revocation, so nothing vested
+    const _beneficiary = await mint.getAccountInfo(pausedBeneficiaryAta);
+    const _vestingAccount = await program.account.vestingAccount.fetch(pausedVesting);
+    assert.ok(_vestingAccount.status.revoked);
+    assert.ok(_beneficiary.amount.toNumber() === 0);
+    assert.ok(_vestingAccount.releasedAmount.toNumber() === 0);
+  });
+
+  it('Stakes the unvested part of a grant for the beneficiary', async () => {
+    const stakingIdl = JSON.parse(fs.readFileSync(STAKING_IDL_PATH));
+    const staking = new anchor.Program(stakingIdl, STAKING_PROGRAM_ID, provider);
+    const stakingPool = await createStakingPool(provider, staking, mint);
+
+    // fund the pool so the position earns something to claim
+    await staking.rpc.fund(new anchor.BN(100_000_000_000), {
+      accounts: {
+        pool: stakingPool.pool,
+        stakingVault: stakingPool.stakingVault,
+        rewardVault: stakingPool.rewardVault,
+        rewardMint: mint.publicKey,
+        funder: owner.publicKey,
+        from: ownerTokenAccount.address,
+        poolSigner: stakingPool.poolSigner,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      },
+    });
+
+    const stakingBeneficiary = anchor.web3.Keypair.generate();
+    const stakingBeneficiaryAta = await mint.createAssociatedTokenAccount(
+      stakingBeneficiary.publicKey
+    );
+    const [stakedVesting, stakedVault] = await findGrantAddresses(
+      program,
+      investorAccount,
+      stakingBeneficiaryAta
+    );
+
+    // an hour-long schedule, so next to nothing vests during the test
+    const startTs = new anchor.BN(Date.now() / 1000);
+    await program.rpc.initialize(
+      new anchor.BN(1_000_000),
+      "Staking Investor",
+      startTs,
+      startTs,
+      new anchor.BN(3600),
+      new anchor.BN(0),
+      new anchor.BN(1),
+      true,
+      {
+        accounts: {
+          owner: owner.publicKey,
+          beneficiary: stakingBeneficiary.publicKey,
+          mint: mint.publicKey,
+          beneficiaryAta: stakingBeneficiaryAta,
+          vaultAccount: stakedVault,
+          vaultAuthority: vaultAuthority,
+          ownerTokenAccount: ownerTokenAccount.address,
+          vestingAccount: stakedVesting,
+          investorAccount: investorAccount,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        },
+      }
+    );
+
+    const [stakingUser, stakingUserNonce] = await PublicKey.findProgramAddress(
+      [stakingBeneficiary.publicKey.toBuffer(), stakingPool.pool.toBuffer()],
+      STAKING_PROGRAM_ID
+    );
+    await staking.rpc.createUser(stakingUserNonce, startTs, {
+      accounts: {
+        pool: stakingPool.pool,
+        user: stakingUser,
+        owner: stakingBeneficiary.publicKey,
+        payer: owner.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      },
+    });
+
+    const stakeAccounts = (authority) => ({
+      authority,
+      vestingAccount: stakedVesting,
+      vaultAccount: stakedVault,
+      mint: mint.publicKey,
+      vaultAuthority: vaultAuthority,
+      beneficiary: stakingBeneficiary.publicKey,
+      stakingPool: stakingPool.pool,
+      stakingVault: stakingPool.stakingVault,
+      stakingRewardVault: stakingPool.rewardVault,
+      stakingUser,
+      stakingPoolSigner: stakingPool.poolSigner,
+      stakingProgram: STAKING_PROGRAM_ID,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    });
+
+    // the CPI only goes through if the encoded `stake_locked` matches the staking program
+    await program.rpc.stakeVesting(new anchor.BN(500_000), {
+      accounts: stakeAccounts(stakingBeneficiary.publicKey),
+      signers: [stakingBeneficiary],
+    });
+
+    let _vestingAccount = await program.account.vestingAccount.fetch(stakedVesting);
+    let _stakingUser = await staking.account.user.fetch(stakingUser);
+    assert.ok(_vestingAccount.stakedAmount.toNumber() === 500_000);
+    assert.ok(_vestingAccount.stakingPool.equals(stakingPool.pool));
+    assert.ok(_stakingUser.balanceLocked.toNumber() === 500_000);
+    assert.ok(_stakingUser.lockAuthority.equals(vaultAuthority));
+    assert.ok((await tokenAmount(provider, stakedVault)) === 500_000);
+
+    // the beneficiary can only unstake what has vested
+    await assertRejected(program.rpc.unstakeVesting(new anchor.BN(500_000), {
+      accounts: stakeAccounts(stakingBeneficiary.publicKey),
+      signers: [stakingBeneficiary],
+    }));
+
+    // the rewards of the position belong to the beneficiary
+    await delay(2000);
+    await staking.rpc.claim(new anchor.BN(Date.now() / 1000), {
+      accounts: {
+        pool: stakingPool.pool,
+        stakingVault: stakingPool.stakingVault,
+        rewardVault: stakingPool.rewardVault,
+        rewardMint: mint.publicKey,
+        user: stakingUser,
+        owner: stakingBeneficiary.publicKey,
+        rewardAccount: stakingBeneficiaryAta,
+        poolSigner: stakingPool.poolSigner,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      },
+      signers: [stakingBeneficiary],
+    });
+    assert.ok((await tokenAmount(provider, stakingBeneficiaryAta)) > 0);
+
+    // the owner can take the whole position back through `unstake_locked`
+    await program.rpc.unstakeVesting(new anchor.BN(500_000), {
+      accounts: stakeAccounts(owner.publicKey),
+    });
+
+    _vestingAccount = await program.account.vestingAccount.fetch(stakedVesting);
+    _stakingUser = await staking.account.user.fetch(stakingUser);
+    assert.ok(_vestingAccount.stakedAmount.toNumber() === 0);
+    assert.ok(_stakingUser.balanceLocked.toNumber() === 0);
+    assert.ok((await tokenAmount(provider, stakedVault)) === 1_000_000);
+  });
+
+  it('Refuses mints the vaults cannot account for', async function () {
+    // Token-2022 only ships with recent test validators
+    if (!(await provider.connection.getAccountInfo(TOKEN_2022_PROGRAM_ID))) {
+      this.skip();
+    }
+
+    const nonTransferableMint = await createNonTransferableMint(provider, mintAuthority);
+    const nonTransferableInvestorAccount = await findInvestorAccount(
+      program,
+      owner.publicKey,
+      nonTransferableMint
+    );
+    await assertRejected(program.rpc.initializeVesting(
+      {
+        accounts: {
+          owner: owner.publicKey,
+          investorAccount: nonTransferableInvestorAccount,
+          mint: nonTransferableMint,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        },
+      }
+    ));
+  });
+
+  it('Grants and releases what a transfer-fee mint delivers', async function () {
+    // Token-2022 only ships with recent test validators
+    if (!(await provider.connection.getAccountInfo(TOKEN_2022_PROGRAM_ID))) {
+      this.skip();
+    }
+
+    // 1% on every transfer
+    const feeMint = await createTransferFeeMint(provider, mintAuthority, 100);
+    const feeOwnerAta = await createToken2022Account(provider, feeMint, owner.publicKey);
+    const feeBeneficiary = anchor.web3.Keypair.generate();
+    const feeBeneficiaryAta = await createToken2022Account(provider, feeMint, feeBeneficiary.publicKey);
+    await mintToken2022(provider, feeMint, feeOwnerAta, mintAuthority, 1_000_000);
+
+    const feeInvestorAccount = await findInvestorAccount(program, owner.publicKey, feeMint);
+    await program.rpc.initializeVesting(
+      {
+        accounts: {
+          owner: owner.publicKey,
+          investorAccount: feeInvestorAccount,
+          mint: feeMint,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        },
+      }
+    );
+
+    const [feeVaultAuthority] = await PublicKey.findProgramAddress(
+      [
+        Buffer.from(anchor.utils.bytes.utf8.encode('vault-authority')),
+        feeInvestorAccount.toBuffer(),
+      ],
+      program.programId
+    );
+    const [feeVesting, feeVault] = await findGrantAddresses(
+      program,
+      feeInvestorAccount,
+      feeBeneficiaryAta
+    );
+
+    const startTs = new anchor.BN(Date.now() / 1000);
+    await program.rpc.initialize(
+      new anchor.BN(1_000_000),
+      "Fee investor",
+      startTs,
+      startTs,
+      new anchor.BN(30),
+      new anchor.BN(1000),
+      new anchor.BN(1),
+      true,
+      {
+        accounts: {
+          owner: owner.publicKey,
+          beneficiary: feeBeneficiary.publicKey,
+          mint: feeMint,
+          beneficiaryAta: feeBeneficiaryAta,
+          vaultAccount: feeVault,
+          vaultAuthority: feeVaultAuthority,
+          ownerTokenAccount: feeOwnerAta,
+          vestingAccount: feeVesting,
+          investorAccount: feeInvestorAccount,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+        },
+      }
+    );
+
+    // only what reached the vault after the fee is granted
+    let _vestingAccount = await program.account.vestingAccount.fetch(feeVesting);
+    assert.ok(_vestingAccount.totalDepositedAmount.toNumber() === 990_000);
+    assert.ok((await tokenAmount(provider, feeVault)) === 990_000);
+
+    await program.rpc.upfront(
+      {
+        accounts: {
+          beneficiary: feeBeneficiary.publicKey,
+          beneficiaryAta: feeBeneficiaryAta,
+          vaultAccount: feeVault,
+          vestingAccount: feeVesting,
+          mint: feeMint,
+          vaultAuthority: feeVaultAuthority,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+        },
+        signers: [feeBeneficiary],
+      }
+    );
+
+    // the grant books the 10% upfront share, the beneficiary receives it less the fee
+    _vestingAccount = await program.account.vestingAccount.fetch(feeVesting);
+    assert.ok(_vestingAccount.releasedAmount.toNumber() === 99_000);
+    assert.ok((await tokenAmount(provider, feeBeneficiaryAta)) === 98_010);
+    assert.ok((await tokenAmount(provider, feeVault)) === 891_000);
+
+    // draining the vault leaves the fees it withheld on the way in and out
+    await program.rpc.revoke(
+      {
+        accounts: {
+          owner: owner.publicKey,
+          vaultAccount: feeVault,
+          vestingAccount: feeVesting,
+          mint: feeMint,
+          ownerTokenAccount: feeOwnerAta,
+          beneficiaryAta: feeBeneficiaryAta,
+          vaultAuthority: feeVaultAuthority,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+        },
+      }
+    );
+    assert.ok((await tokenAmount(provider, feeVault)) === 0);
+
+    // closing harvests them to the mint first
+    await program.rpc.closeVesting(
+      {
+        accounts: {
+          owner: owner.publicKey,
+          investorAccount: feeInvestorAccount,
+          vestingAccount: feeVesting,
+          vaultAccount: feeVault,
+          mint: feeMint,
+          vaultAuthority: feeVaultAuthority,
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+        },
+      }
+    );
+    assert.ok((await provider.connection.getAccountInfo(feeVault)) === null);
+    assert.ok((await mintWithheldAmount(provider, feeMint)) > 0);
   });
 });
 
+// Investor account of the project that grants `mint` on behalf of `owner`
+async function findInvestorAccount(program, owner, mint) {
+  const [investorAccount] = await PublicKey.findProgramAddress(
+    [
+      Buffer.from(anchor.utils.bytes.utf8.encode('investor-account')),
+      owner.toBuffer(),
+      mint.toBuffer(),
+    ],
+    program.programId
+  );
+
+  return investorAccount;
+}
+
+// A fixed-rate staking pool of `mint`, staked and rewarded in the same token
+async function createStakingPool(provider, staking, mint) {
+  const pool = anchor.web3.Keypair.generate();
+  const [poolSigner, poolNonce] = await PublicKey.findProgramAddress(
+    [pool.publicKey.toBuffer()],
+    STAKING_PROGRAM_ID
+  );
+  const poolTokenAta = await mint.createAssociatedTokenAccount(pool.publicKey);
+  const [stakingVault] = await PublicKey.findProgramAddress(
+    [Buffer.from(anchor.utils.bytes.utf8.encode('staking-vault')), poolTokenAta.toBuffer()],
+    STAKING_PROGRAM_ID
+  );
+  const [rewardVault] = await PublicKey.findProgramAddress(
+    [Buffer.from(anchor.utils.bytes.utf8.encode('reward-vault')), poolTokenAta.toBuffer()],
+    STAKING_PROGRAM_ID
+  );
+
+  await staking.rpc.initializeMainPool(
+    poolNonce,
+    new anchor.BN(10),
+    { fixedRate: {} },
+    new anchor.BN(0),
+    new anchor.BN(0),
+    {
+      accounts: {
+        authority: provider.wallet.publicKey,
+        stakingMint: mint.publicKey,
+        stakingVault,
+        rewardMint: mint.publicKey,
+        rewardVault,
+        poolTokenAta,
+        poolSigner,
+        pool: pool.publicKey,
+        stakingTokenProgram: TOKEN_PROGRAM_ID,
+        rewardTokenProgram: TOKEN_PROGRAM_ID,
+        rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      },
+      signers: [pool],
+    }
+  );
+
+  return { pool: pool.publicKey, poolSigner, stakingVault, rewardVault };
+}
+
+// Vesting account and vault of the next grant created in the investor account
+async function findGrantAddresses(program, investorAccount, beneficiaryAta) {
+  const _investorAccount = await program.account.investorAccount.fetch(investorAccount);
+
+  const [vesting] = await PublicKey.findProgramAddress(
+    [
+      investorAccount.toBuffer(),
+      beneficiaryAta.toBuffer(),
+      _investorAccount.grantCount.toArrayLike(Buffer, 'le', 8),
+    ],
+    program.programId
+  );
+  const [vault] = await PublicKey.findProgramAddress(
+    [
+      Buffer.from(anchor.utils.bytes.utf8.encode('token-vault')),
+      vesting.toBuffer(),
+    ],
+    program.programId
+  );
+
+  return [vesting, vault];
+}
+
+// A Token-2022 mint with 0 decimals and a transfer fee of `feeBps`, built from raw
+// instructions as @solana/spl-token predates Token-2022
+async function createTransferFeeMint(provider, authority, feeBps) {
+  const mint = anchor.web3.Keypair.generate();
+  // base mint padded to 165 bytes, account type, then the TransferFeeConfig extension
+  const space = 165 + 1 + 4 + 108;
+
+  const initializeTransferFeeConfig = Buffer.alloc(2 + 33 + 33 + 2 + 8);
+  initializeTransferFeeConfig.writeUInt8(26, 0); // TransferFeeExtension
+  initializeTransferFeeConfig.writeUInt8(0, 1); // InitializeTransferFeeConfig
+  initializeTransferFeeConfig.writeUInt8(1, 2);
+  authority.publicKey.toBuffer().copy(initializeTransferFeeConfig, 3);
+  initializeTransferFeeConfig.writeUInt8(1, 35);
+  authority.publicKey.toBuffer().copy(initializeTransferFeeConfig, 36);
+  initializeTransferFeeConfig.writeUInt16LE(feeBps, 68);
+  new anchor.BN(1_000_000_000).toArrayLike(Buffer, 'le', 8).copy(initializeTransferFeeConfig, 70);
+
+  const initializeMint = Buffer.alloc(1 + 1 + 32 + 1);
+  initializeMint.writeUInt8(0, 0); // InitializeMint
+  initializeMint.writeUInt8(0, 1);
+  authority.publicKey.toBuffer().copy(initializeMint, 2);
+
+  const tx = new Transaction().add(
+    SystemProgram.createAccount({
+      fromPubkey: provider.wallet.publicKey,
+      newAccountPubkey: mint.publicKey,
+      space,
+      lamports: await provider.connection.getMinimumBalanceForRentExemption(space),
+      programId: TOKEN_2022_PROGRAM_ID,
+    }),
+    new anchor.web3.TransactionInstruction({
+      programId: TOKEN_2022_PROGRAM_ID,
+      keys: [{ pubkey: mint.publicKey, isSigner: false, isWritable: true }],
+      data: initializeTransferFeeConfig,
+    }),
+    new anchor.web3.TransactionInstruction({
+      programId: TOKEN_2022_PROGRAM_ID,
+      keys: [
+        { pubkey: mint.publicKey, isSigner: false, isWritable: true },
+        { pubkey: anchor.web3.SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false },
+      ],
+      data: initializeMint,
+    })
+  );
+  await provider.send(tx, [mint]);
+
+  return mint.publicKey;
+}
+
+// A Token-2022 mint with 0 decimals whose tokens cannot be transferred
+async function createNonTransferableMint(provider, authority) {
+  const mint = anchor.web3.Keypair.generate();
+  // base mint padded to 165 bytes, account type, then the empty NonTransferable extension
+  const space = 165 + 1 + 4;
+
+  const initializeMint = Buffer.alloc(1 + 1 + 32 + 1);
+  initializeMint.writeUInt8(0, 0); // InitializeMint
+  initializeMint.writeUInt8(0, 1);
+  authority.publicKey.toBuffer().copy(initializeMint, 2);
+
+  const tx = new Transaction().add(
+    SystemProgram.createAccount({
+      fromPubkey: provider.wallet.publicKey,
+      newAccountPubkey: mint.publicKey,
+      space,
+      lamports: await provider.connection.getMinimumBalanceForRentExemption(space),
+      programId: TOKEN_2022_PROGRAM_ID,
+    }),
+    new anchor.web3.TransactionInstruction({
+      programId: TOKEN_2022_PROGRAM_ID,
+      keys: [{ pubkey: mint.publicKey, isSigner: false, isWritable: true }],
+      data: Buffer.from([32]), // InitializeNonTransferableMint
+    }),
+    new anchor.web3.TransactionInstruction({
+      programId: TOKEN_2022_PROGRAM_ID,
+      keys: [
+        { pubkey: mint.publicKey, isSigner: false, isWritable: true },
+        { pubkey: anchor.web3.SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false },
+      ],
+      data: initializeMint,
+    })
+  );
+  await provider.send(tx, [mint]);
+
+  return mint.publicKey;
+}
+
+// A Token-2022 account of `owner` for a transfer-fee mint
+async function createToken2022Account(provider, mint, owner) {
+  const account = anchor.web3.Keypair.generate();
+  // base account, account type, then the TransferFeeAmount extension
+  const space = 165 + 1 + 4 + 8;
+
+  const initializeAccount3 = Buffer.alloc(1 + 32);
+  initializeAccount3.writeUInt8(18, 0); // InitializeAccount3
+  owner.toBuffer().copy(initializeAccount3, 1);
+
+  const tx = new Transaction().add(
+    SystemProgram.createAccount({
+      fromPubkey: provider.wallet.publicKey,
+      newAccountPubkey: account.publicKey,
+      space,
+      lamports: await provider.connection.getMinimumBalanceForRentExemption(space),
+      programId: TOKEN_2022_PROGRAM_ID,
+    }),
+    new anchor.web3.TransactionInstruction({
+      programId: TOKEN_2022_PROGRAM_ID,
+      keys: [
+        { pubkey: account.publicKey, isSigner: false, isWritable: true },
+        { pubkey: mint, isSigner: false, isWritable: false },
+      ],
+      data: initializeAccount3,
+    })
+  );
+  await provider.send(tx, [account]);
+
+  return account.publicKey;
+}
+
+async function mintToken2022(provider, mint, account, authority, amount) {
+  const mintTo = Buffer.alloc(1 + 8);
+  mintTo.writeUInt8(7, 0); // MintTo
+  new anchor.BN(amount).toArrayLike(Buffer, 'le', 8).copy(mintTo, 1);
+
+  const tx = new Transaction().add(
+    new anchor.web3.TransactionInstruction({
+      programId: TOKEN_2022_PROGRAM_ID,
+      keys: [
+        { pubkey: mint, isSigner: false, isWritable: true },
+        { pubkey: account, isSigner: false, isWritable: true },
+        { pubkey: authority.publicKey, isSigner: true, isWritable: false },
+      ],
+      data: mintTo,
+    })
+  );
+  await provider.send(tx, [authority]);
+}
+
+// The transfer fees harvested to a transfer-fee mint: its TransferFeeConfig extension starts
+// at byte 170 with the two authorities ahead of the withheld amount
+async function mintWithheldAmount(provider, mint) {
+  const info = await provider.connection.getAccountInfo(mint);
+  return new anchor.BN(info.data.slice(234, 242), 'le').toNumber();
+}
+
+// The balance of a token account of either token program
+async function fundAddress(provider, address, lamports) {
+  const tx = new anchor.web3.Transaction().add(
+    anchor.web3.SystemProgram.transfer({
+      fromPubkey: provider.wallet.publicKey,
+      toPubkey: address,
+      lamports,
+    })
+  );
+  await provider.send(tx);
+}
+
+async function tokenAmount(provider, account) {
+  const info = await provider.connection.getAccountInfo(account);
+  return new anchor.BN(info.data.slice(64, 72), 'le').toNumber();
+}
+
+// Fails the test unless the transaction is rejected
+async function assertRejected(tx) {
+  try {
+    await tx;
+  } catch (err) {
+    return;
+  }
+  assert.fail('transaction should have been rejected');
+}
+
 function delay(ms) {
   return new Promise(resolve => setTimeout(resolve, ms));
 }
\ No newline at end of file